@@ -1,72 +1,49 @@
 #![windows_subsystem = "windows"]
 
-use druid::commands::QUIT_APP;
+use argh::FromArgs;
+use chrono::{Duration, Local, TimeZone, Utc};
+use druid::commands::{QUIT_APP, SAVE_FILE_AS, SHOW_SAVE_PANEL};
 use druid::keyboard_types::Key;
 use druid::text::format::{Formatter, Validation, ValidationError};
 use druid::text::selection::Selection;
 use druid::widget::prelude::*;
-use druid::widget::{Align, Button, Controller, Flex, Label, LineBreaking, List, Padding, Scroll, SizedBox, Split, TextBox, ValueTextBox};
-use druid::{AppLauncher, Command, Data, Lens, LocalizedString, MenuDesc, MenuItem, Selector, Target, Widget, WidgetExt, WindowDesc};
-use fluorite::parse::{clean_input, get_last_input, parse_input, RollInformation, Rule, VALID_INPUT_CHARS};
-use lazy_static::lazy_static;
-use serde::{Deserialize, Serialize};
-use std::env::current_exe;
+use druid::widget::{Align, Button, Controller, EnvScope, Flex, Label, LineBreaking, List, Padding, Scroll, SizedBox, Split, Stepper, TextBox, ValueTextBox};
+use druid::{theme, AppLauncher, Color, Command, Data, FileDialogOptions, FileSpec, Key as EnvKey, Lens, LocalizedString, MenuDesc, MenuItem, Selector, Target, Widget, WidgetExt, WindowDesc};
+use fluorite::export::{export, ExportFormat};
+use fluorite::parse::{clean_input, get_last_input, parse_with_limits, EvaluationContext, NaturalResult, RollInformation, Rule, VALID_INPUT_CHARS};
+use fluorite::registry::{DiceDefinitions, DiceRegistry};
+use fluorite::systems::GameSystem;
+use fluorite::error::StorageError;
+use fluorite::session::{LogEntry, LogFilter, RngSource};
+use fluorite::storage::{Config, DataDir, History, RollShortcut, Schema, Shortcuts, Store};
 use std::error::Error;
-use std::fs::{create_dir_all, read_to_string, write};
-use std::path::PathBuf;
+use std::fs::write;
+use std::path::{Path, PathBuf};
+use std::process::exit;
 use std::sync::Arc;
 
 ////////////////
 //   Consts   //
 ////////////////
 
-lazy_static! {
-    static ref DATA_DIR: PathBuf = {
-        let mut path = current_exe().unwrap(); // Replace with real error-handling
-        path.pop();
-        path.push("fluorite_data");
+const LATEST_OUTPUT_COLOR: EnvKey<Color> = EnvKey::new("fluorite.latest-output-color");
+const CRITICAL_HIT_COLOR: Color = Color::rgb8(0x4c, 0xc2, 0x4c);
+const CRITICAL_MISS_COLOR: Color = Color::rgb8(0xe0, 0x4c, 0x4c);
 
-        path
-    };
-    static ref CONFIG_PATH: PathBuf = {
-        let mut path = DATA_DIR.clone();
-        path.push("config.json");
-
-        path
-    };
-    static ref HISTORY_PATH: PathBuf = {
-        let mut path = DATA_DIR.clone();
-        path.push("history.json");
-
-        path
-    };
-    static ref SHORTCUTS_PATH: PathBuf = {
-        let mut path = DATA_DIR.clone();
-        path.push("shortcuts.json");
-
-        path
-    };
-}
+const EXPORT_HISTORY: Selector<ExportRange> = Selector::new("fluorite.export-history");
+const EXPORT_FILE_TYPES: [FileSpec; 3] = [FileSpec::new("Markdown", &["md"]), FileSpec::new("HTML", &["html"]), FileSpec::new("CSV", &["csv"])];
+const EXPORT_RECENT_ROLLS: usize = 50;
 
 /////////////////
 //   Structs   //
 /////////////////
 
-#[derive(Clone, Data, Deserialize, Serialize)]
-struct DiceCalculatorConfig {
-    max_history_entries: u64,
-    save_history: bool,
-    save_shortcuts: bool,
-}
-
-impl DiceCalculatorConfig {
-    fn new() -> DiceCalculatorConfig {
-        DiceCalculatorConfig {
-            max_history_entries: 100,
-            save_history: true,
-            save_shortcuts: true,
-        }
-    }
+/// A dice-rolling calculator.
+#[derive(FromArgs)]
+struct Args {
+    /// directory to keep settings, history, and shortcuts in, instead of the usual per-user or portable location
+    #[argh(option)]
+    data_dir: Option<PathBuf>,
 }
 
 enum CalcButton {
@@ -87,6 +64,8 @@ enum CalcButton {
     D10,
     D12,
     D20,
+    Crit,
+    CustomDie(String),
     Plus,
     Minus,
     Times,
@@ -99,78 +78,176 @@ enum CalcButton {
     Clear,
     Backspace,
     Roll,
-    PlaceholderNotCurrentlyInUse,
 }
 
-#[derive(Clone, Data, Deserialize, Serialize)]
-struct RollShortcut {
-    name: String,
-    roll: String,
+/// Which rolls from the session log an item in the File > Export History menu exports.
+#[derive(Clone, Copy)]
+enum ExportRange {
+    Today,
+    LastWeek,
+    Recent,
+    All,
 }
 
+impl ExportRange {
+    fn filter(self) -> LogFilter {
+        match self {
+            ExportRange::Today => {
+                let midnight = Local::now().naive_local().date().and_hms_opt(0, 0, 0).unwrap();
+                LogFilter {
+                    since: Local.from_local_datetime(&midnight).earliest().map(|time| time.with_timezone(&Utc)),
+                    ..LogFilter::default()
+                }
+            }
+            ExportRange::LastWeek => LogFilter {
+                since: Some(Utc::now() - Duration::days(7)),
+                ..LogFilter::default()
+            },
+            ExportRange::Recent => LogFilter {
+                last: Some(EXPORT_RECENT_ROLLS),
+                ..LogFilter::default()
+            },
+            ExportRange::All => LogFilter::default(),
+        }
+    }
+}
+
+/// A shortcut in the shortcut list, which needs to be [`Data`] to be shown there.
+#[derive(Clone, Data)]
+struct ShortcutItem(#[data(same_fn = "PartialEq::eq")] RollShortcut);
+
 #[derive(Clone, Data, Lens)]
 struct DiceCalculator {
-    config: DiceCalculatorConfig,
+    #[data(same_fn = "PartialEq::eq")]
+    config: Config,
     current_input: String,
     stored_input: String,
-    history: Arc<Vec<(String, Result<RollInformation, String>)>>,
+    history: Arc<History>,
     steps_back_in_history: usize,
-    shortcuts: Arc<Vec<RollShortcut>>,
+    shortcuts: Arc<Vec<ShortcutItem>>,
     new_shortcut_name: String,
     new_shortcut_text: String,
+    system_parameter: f64,
+    dice: Arc<DiceRegistry>,
+    #[data(same_fn = "PartialEq::eq")]
+    data_dir: Arc<DataDir>,
 }
 
 impl DiceCalculator {
-    fn new(config: DiceCalculatorConfig) -> DiceCalculator {
+    /// Sets up the calculator, showing any `problems` met while loading saved data in its history.
+    fn new(config: Config, dice: DiceRegistry, data_dir: DataDir, mut problems: Vec<String>) -> DiceCalculator {
+        let mut history = match config.save_history {
+            true => load_history(&data_dir, config.max_history_entries, &mut problems),
+            false => Vec::new(),
+        };
+        let shortcuts = match config.save_shortcuts {
+            true => load_or_recover(&data_dir.shortcuts(), &mut problems).into_iter().map(ShortcutItem).collect(),
+            false => Vec::new(),
+        };
+        history.extend(problems.into_iter().map(|problem| (String::from("Loading saved data"), Err(problem))));
+
         DiceCalculator {
             config: config.clone(),
             current_input: String::new(),
             stored_input: String::new(),
-            history: match config.save_history {
-                true => load_history(),
-                false => Arc::new(Vec::new()),
-            },
+            history: Arc::new(history),
             steps_back_in_history: 0,
-            shortcuts: match config.save_history {
-                true => load_shortcuts(),
-                false => Arc::new(Vec::new()),
-            },
+            shortcuts: Arc::new(shortcuts),
             new_shortcut_name: String::new(),
             new_shortcut_text: String::new(),
+            system_parameter: 2.,
+            dice: Arc::new(dice),
+            data_dir: Arc::new(data_dir),
+        }
+    }
+    fn evaluation_context(&self) -> EvaluationContext {
+        EvaluationContext {
+            crit_mode: self.config.crit_mode,
+            dice: self.dice.clone(),
+            limits: self.config.limits,
+            summarize_above: self.config.summarize_above,
+            arithmetic: self.config.arithmetic,
+            ..EvaluationContext::new()
         }
     }
-    fn add_to_history(&mut self, input: String, output: Result<RollInformation, String>) {
+    /// Adds a roll to the history, and to the session log if history is being saved.
+    fn add_to_history(&mut self, entry: LogEntry) {
         let history = Arc::make_mut(&mut self.history);
-        history.push((input, output));
+        history.push((entry.input.clone(), entry.result.clone()));
         while history.len() as u64 > self.config.max_history_entries {
             let _ = history.drain(0..1);
         }
         if self.config.save_history {
-            save_history(&self);
+            report_save_error(self.data_dir.session_log().append(&entry));
+        }
+    }
+    /// A log entry for a roll made just now, under the configured profile.
+    fn log_entry(&self, input: String, result: Result<RollInformation, String>, rng: Option<RngSource>, shortcut: Option<&str>) -> LogEntry {
+        LogEntry {
+            profile: self.config.profile.clone(),
+            shortcut: shortcut.map(String::from),
+            ..LogEntry::new(input, result, rng)
         }
     }
+    /// Rolls `input`, recording it in history in canonical form if it parses, along with the shortcut it came from if any.
+    fn roll_input(&mut self, input: &str, shortcut: Option<&str>) {
+        let entry = match parse_with_limits(input, &self.config.limits) {
+            Ok(roll) => {
+                let (rng_source, mut rng) = RngSource::from_entropy();
+                let result = roll.roll_with_rng(&self.evaluation_context(), &mut rng).map_err(|e| e.to_string());
+                self.log_entry(roll.to_string(), result, Some(rng_source), shortcut)
+            }
+            Err(e) => self.log_entry(String::from(input), Err(e.to_string()), None, shortcut),
+        };
+        self.add_to_history(entry);
+    }
     fn roll(&mut self) {
         if !self.current_input.is_empty() {
-            self.add_to_history(self.current_input.clone(), parse_input(&self.current_input));
+            self.roll_input(&self.current_input.clone(), None);
             self.current_input = String::new();
             self.stored_input = String::new();
             self.steps_back_in_history = 0;
         }
     }
     fn roll_from_shortcut(&mut self, shortcut: &RollShortcut) {
-        self.add_to_history(shortcut.roll.clone(), parse_input(&shortcut.roll));
+        self.roll_input(&shortcut.roll, Some(&shortcut.name));
+        if self.steps_back_in_history != 0 {
+            self.current_input = self.stored_input.clone()
+        }
+        self.steps_back_in_history = 0;
+    }
+    fn roll_system(&mut self, system: GameSystem) {
+        let parameter = self.system_parameter as i64;
+        let (rng_source, mut rng) = RngSource::from_entropy();
+        let result = system.roll_with_rng(parameter, &self.config.limits, &mut rng).map_err(|e| e.to_string());
+        let entry = self.log_entry(format!("{} ({})", system.name(), parameter), result, Some(rng_source), None);
+        self.add_to_history(entry);
         if self.steps_back_in_history != 0 {
             self.current_input = self.stored_input.clone()
         }
         self.steps_back_in_history = 0;
     }
+    /// Exports the logged rolls in `range` to `path`, in the format its extension names, noting in the history if it can't.
+    fn export_history(&mut self, range: ExportRange, path: &Path) {
+        let format = ExportFormat::from_path(path).unwrap_or(ExportFormat::Markdown);
+        let exported = match self.data_dir.session_log().query(&range.filter()) {
+            Ok(entries) => write(path, export(&entries, format, &self.config.display, &Local)).map_err(|e| format!("Couldn't write {}: {}", path.display(), e)),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = exported {
+            Arc::make_mut(&mut self.history).push((String::from("Exporting history"), Err(e)));
+        }
+    }
     fn add_shortcut(_ctx: &mut EventCtx, data: &mut Self, _env: &Env) {
         let new_shortcut = RollShortcut {
             name: data.new_shortcut_name.clone(),
-            roll: data.new_shortcut_text.clone(),
+            roll: match parse_with_limits(&data.new_shortcut_text, &data.config.limits) {
+                Ok(roll) => roll.to_string(),
+                Err(_) => data.new_shortcut_text.clone(),
+            },
         };
-        if !data.shortcuts.iter().any(|shortcut| shortcut.name == new_shortcut.name || new_shortcut.name == "") {
-            Arc::make_mut(&mut data.shortcuts).insert(0, new_shortcut);
+        if !data.shortcuts.iter().any(|ShortcutItem(shortcut)| shortcut.name == new_shortcut.name || new_shortcut.name == "") {
+            Arc::make_mut(&mut data.shortcuts).insert(0, ShortcutItem(new_shortcut));
             data.new_shortcut_name = String::new();
             data.new_shortcut_text = String::new();
         }
@@ -180,7 +257,10 @@ impl DiceCalculator {
     }
 }
 
-struct DiceCalcEventHandler;
+struct DiceCalcEventHandler {
+    /// The rolls to export once the save dialog opened from the File > Export History menu gives a file to export them to.
+    export_range: Option<ExportRange>,
+}
 
 impl<W: Widget<DiceCalculator>> Controller<DiceCalculator, W> for DiceCalcEventHandler {
     fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut DiceCalculator, env: &Env) {
@@ -231,10 +311,18 @@ impl<W: Widget<DiceCalculator>> Controller<DiceCalculator, W> for DiceCalcEventH
                     data.roll_from_shortcut(shortcut);
                 } else if command.is::<RollShortcut>(Selector::new("ShortcutDelete")) {
                     let name_to_delete = command.get_unchecked::<RollShortcut>(Selector::new("ShortcutDelete")).name.clone();
-                    Arc::make_mut(&mut data.shortcuts).retain(|shortcut| shortcut.name != name_to_delete);
+                    Arc::make_mut(&mut data.shortcuts).retain(|ShortcutItem(shortcut)| shortcut.name != name_to_delete);
                     if data.config.save_shortcuts {
                         save_shortcuts(&data);
                     }
+                } else if let Some(range) = command.get(EXPORT_HISTORY) {
+                    self.export_range = Some(*range);
+                    let options = FileDialogOptions::new().title("Export History").allowed_types(EXPORT_FILE_TYPES.to_vec()).default_type(EXPORT_FILE_TYPES[0]).default_name("rolls.md");
+                    ctx.submit_command(SHOW_SAVE_PANEL.with(options));
+                } else if let Some(file_info) = command.get(SAVE_FILE_AS) {
+                    if let Some(range) = self.export_range.take() {
+                        data.export_history(range, file_info.path());
+                    }
                 }
             }
             _ => (),
@@ -282,58 +370,87 @@ impl Formatter<String> for DiceTextFormatter {
 //   Helper Functions   //
 //////////////////////////
 
-fn ensure_data_dir_exists() {
-    if !DATA_DIR.as_path().exists() {
-        create_dir_all(&*DATA_DIR).unwrap()
+/// Reports a file saved by a newer version of Fluorite and exits, since carrying on would mean saving over it.
+fn exit_for_newer_version(error: &StorageError) -> ! {
+    eprintln!("fluorite-gui: {}. Use that version of Fluorite instead, or pick another data directory with --data-dir.", error);
+    exit(1);
+}
+
+/// Loads `store`, falling back on its latest readable backup and then its default if it can't be read, and noting why in `problems`.
+///
+/// A file from a newer version of Fluorite is never replaced this way; it's reported and the GUI exits instead.
+fn load_or_recover<T: Schema>(store: &Store<T>, problems: &mut Vec<String>) -> T {
+    match store.load() {
+        Ok(value) => value,
+        Err(e @ StorageError::NewerVersion { .. }) => exit_for_newer_version(&e),
+        Err(e) => match store.restore_backup() {
+            Some(value) => {
+                problems.push(format!("{}. Restored the latest backup instead.", e));
+                value
+            }
+            None => {
+                problems.push(format!("{}. There was no backup to restore, so it was started afresh.", e));
+                T::default()
+            }
+        },
     }
 }
 
-fn load_config() -> DiceCalculatorConfig {
-    match read_to_string(&*CONFIG_PATH) {
-        Ok(config_as_json) => match serde_json::from_str(&config_as_json) {
-            Ok(config) => config,
-            Err(_) => DiceCalculatorConfig::new(),
-        },
-        Err(_) => DiceCalculatorConfig::new(),
+/// The last `max_entries` rolls in the session log, which starts with the rolls in an older history file if there are any.
+fn load_history(data_dir: &DataDir, max_entries: u64, problems: &mut Vec<String>) -> History {
+    let log = data_dir.session_log();
+    match log.import_history(&data_dir.history()) {
+        Ok(_) => (),
+        Err(e @ StorageError::NewerVersion { .. }) => exit_for_newer_version(&e),
+        Err(e) => problems.push(format!("{}. The history from before the session log wasn't imported.", e)),
+    }
+    match log.history(max_entries) {
+        Ok(history) => history,
+        Err(e) => {
+            problems.push(e.to_string());
+            Vec::new()
+        }
     }
 }
 
-fn load_history() -> Arc<Vec<(String, Result<RollInformation, String>)>> {
-    match read_to_string(&*HISTORY_PATH) {
-        Ok(history_as_json) => match serde_json::from_str(&history_as_json) {
-            Ok(history) => history,
-            Err(_) => Arc::new(Vec::new()),
-        },
-        Err(_) => Arc::new(Vec::new()),
+/// The built-in dice along with those in dice.json if there is one, and the names of the custom dice which made it into the registry.
+fn load_dice(data_dir: &DataDir, problems: &mut Vec<String>) -> (DiceRegistry, Vec<String>) {
+    let path = data_dir.dice_path();
+    if !path.exists() {
+        return (DiceRegistry::new(), Vec::new());
+    }
+    let mut dice = DiceRegistry::new();
+    match DiceDefinitions::load(&path) {
+        Ok(definitions) => {
+            let custom_dice = definitions.dice.iter().map(|die| die.name.clone()).collect();
+            match dice.register_definitions(definitions) {
+                Ok(()) => (dice, custom_dice),
+                Err(e) => {
+                    problems.push(format!("{}. Only the built-in dice are available.", e.trim_end_matches('.')));
+                    (DiceRegistry::new(), Vec::new())
+                }
+            }
+        }
+        Err(e) => {
+            problems.push(format!("{}. Only the built-in dice are available.", e.trim_end_matches('.')));
+            (DiceRegistry::new(), Vec::new())
+        }
     }
 }
 
-fn load_shortcuts() -> Arc<Vec<RollShortcut>> {
-    match read_to_string(&*SHORTCUTS_PATH) {
-        Ok(shortcuts_as_json) => match serde_json::from_str(&shortcuts_as_json) {
-            Ok(shortcuts) => shortcuts,
-            Err(_) => Arc::new(Vec::new()),
-        },
-        Err(_) => Arc::new(Vec::new()),
+fn report_save_error(result: Result<(), StorageError>) {
+    if let Err(e) = result {
+        eprintln!("Failed to save: {}", e);
     }
 }
 
 fn save_config(calc: &DiceCalculator) {
-    ensure_data_dir_exists();
-    let config_as_json = serde_json::to_string(&calc.config).unwrap();
-    write(&*CONFIG_PATH, config_as_json).unwrap();
-}
-
-fn save_history(calc: &DiceCalculator) {
-    ensure_data_dir_exists();
-    let history_as_json = serde_json::to_string(&calc.history).unwrap();
-    write(&*HISTORY_PATH, history_as_json).unwrap();
+    report_save_error(calc.data_dir.config().save(&calc.config));
 }
 
 fn save_shortcuts(calc: &DiceCalculator) {
-    ensure_data_dir_exists();
-    let shortcuts_as_json = serde_json::to_string(&calc.shortcuts).unwrap();
-    write(&*SHORTCUTS_PATH, shortcuts_as_json).unwrap();
+    let shortcuts: Shortcuts = calc.shortcuts.iter().map(|ShortcutItem(shortcut)| shortcut.clone()).collect();
+    report_save_error(calc.data_dir.shortcuts().save(&shortcuts));
 }
 
 //////////////////////
@@ -344,7 +461,7 @@ fn build_calc_button(button: CalcButton, label: &str) -> impl Widget<DiceCalcula
     SizedBox::new(Label::new(label).center())
         .width(100.)
         .height(100.)
-        .on_click(move |_ctx, data: &mut DiceCalculator, _env| match button {
+        .on_click(move |_ctx, data: &mut DiceCalculator, _env| match &button {
             CalcButton::Zero => data.current_input.push('0'),
             CalcButton::One => data.current_input.push('1'),
             CalcButton::Two => data.current_input.push('2'),
@@ -380,6 +497,11 @@ fn build_calc_button(button: CalcButton, label: &str) -> impl Widget<DiceCalcula
                 Some(rule) if rule == Rule::number => data.current_input.push_str("d20"),
                 _ => data.current_input.push_str("1d20"),
             }
+            CalcButton::Crit => data.current_input.push_str("crit("),
+            CalcButton::CustomDie(name) => match get_last_input(&data.current_input).1 {
+                Some(Rule::number) => data.current_input.push_str(&format!("d{}", name)),
+                _ => data.current_input.push_str(&format!("1d{}", name)),
+            }
             CalcButton::Plus => data.current_input.push('+'),
             CalcButton::Minus => data.current_input.push('-'),
             CalcButton::Times => data.current_input.push('*'),
@@ -401,7 +523,6 @@ fn build_calc_button(button: CalcButton, label: &str) -> impl Widget<DiceCalcula
             CalcButton::Roll => if !data.current_input.is_empty() {
                 data.roll()
             }
-            CalcButton::PlaceholderNotCurrentlyInUse => (),
         })
 }
 
@@ -413,8 +534,17 @@ fn build_current_input_display() -> impl Widget<DiceCalculator> {
     )
 }
 
-fn build_main_calculator_display() -> impl Widget<DiceCalculator> {
-    Flex::column()
+fn build_custom_dice_buttons(custom_dice: &[String]) -> impl Widget<DiceCalculator> {
+    let mut buttons = Flex::column();
+    for name in custom_dice {
+        buttons.add_child(build_calc_button(CalcButton::CustomDie(name.clone()), &format!("d{}", name)));
+    }
+
+    Scroll::new(buttons).vertical()
+}
+
+fn build_main_calculator_display(custom_dice: &[String]) -> impl Widget<DiceCalculator> {
+    let main_buttons = Flex::column()
         .with_flex_child(
             Flex::row()
                 .with_child(build_calc_button(CalcButton::D20, "d20"))
@@ -463,31 +593,58 @@ fn build_main_calculator_display() -> impl Widget<DiceCalculator> {
         .with_flex_child(
             Flex::row()
                 .with_child(build_calc_button(CalcButton::D4, "d4"))
-                .with_child(build_calc_button(CalcButton::PlaceholderNotCurrentlyInUse, ""))
+                .with_child(build_calc_button(CalcButton::Crit, "crit"))
                 .with_child(build_calc_button(CalcButton::Zero, "0"))
                 .with_child(build_calc_button(CalcButton::Decimal, "."))
                 .with_child(build_calc_button(CalcButton::Roll, "[Roll]")),
             1.,
-        )
+        );
+
+    Flex::row().with_flex_child(main_buttons, 1.).with_child(build_custom_dice_buttons(custom_dice))
 }
 
-fn build_main_column() -> impl Widget<DiceCalculator> {
-    Split::rows(build_current_input_display(), build_main_calculator_display()).split_point(0.15).solid_bar(true)
+fn build_main_column(custom_dice: &[String]) -> impl Widget<DiceCalculator> {
+    Split::rows(build_current_input_display(), build_main_calculator_display(custom_dice)).split_point(0.15).solid_bar(true)
 }
 
 fn build_latest_output_display() -> impl Widget<DiceCalculator> {
-    Align::centered(
-        Label::<DiceCalculator>::dynamic(|calc, _env| match calc.history.last() {
-            None => String::from("Result"),
-            Some(roll_result) => match &roll_result.1 {
-                Err(_) => String::from("Error"),
-                Ok(info) => format!("{}", info.value),
-            },
-        })
-        .with_text_size(50.)
+    EnvScope::new(
+        |env, calc: &DiceCalculator| {
+            let color = match calc.history.last() {
+                Some((_, Ok(info))) => match info.natural() {
+                    Some(NaturalResult::CriticalHit) => CRITICAL_HIT_COLOR,
+                    Some(NaturalResult::CriticalMiss) => CRITICAL_MISS_COLOR,
+                    None => env.get(theme::LABEL_COLOR),
+                },
+                _ => env.get(theme::LABEL_COLOR),
+            };
+            env.set(LATEST_OUTPUT_COLOR, color);
+        },
+        Align::centered(
+            Label::<DiceCalculator>::dynamic(|calc, _env| match calc.history.last() {
+                None => String::from("Result"),
+                Some(roll_result) => match &roll_result.1 {
+                    Err(_) => String::from("Error"),
+                    Ok(info) => calc.config.display.format_value(&info.value),
+                },
+            })
+            .with_text_size(50.)
+            .with_text_color(LATEST_OUTPUT_COLOR)
+        ),
     )
 }
 
+fn format_natural(info: &RollInformation) -> String {
+    match info.attack_die {
+        Some(attack_die) => match attack_die.natural() {
+            Some(NaturalResult::CriticalHit) => format!("*** Natural {}: critical hit! ***\n", attack_die.face),
+            Some(NaturalResult::CriticalMiss) => format!("*** Natural {}: critical miss! ***\n", attack_die.face),
+            None => String::new(),
+        },
+        None => String::new(),
+    }
+}
+
 fn build_history_display() -> impl Widget<DiceCalculator> {
     Scroll::new(
         Label::<DiceCalculator>::dynamic(|calc, _| {
@@ -495,7 +652,13 @@ fn build_history_display() -> impl Widget<DiceCalculator> {
             for roll_result in calc.history.iter().rev() {
                 match roll_result {
                     (input, Err(e)) => history.push_str(&format!("Input: {}\nError: {}\n\n", input, e)),
-                    (input, Ok(info)) => history.push_str(&format!("Input: {}\nRolled: {}\nResult: {}\n\n", input, info.processed_string, info.value)),
+                    (input, Ok(info)) => {
+                        history.push_str(&format!("Input: {}\nRolled: {}\nResult: {}\n", input, info.processed_string, calc.config.display.format_value(&info.value)));
+                        if let Some(outcome) = &info.outcome {
+                            history.push_str(&format!("Outcome: {}\n", outcome));
+                        }
+                        history.push_str(&format!("{}\n", format_natural(info)));
+                    }
                 }
             }
             history
@@ -522,38 +685,63 @@ fn build_shortcut_list() -> impl Widget<DiceCalculator> {
         Scroll::new(
             List::new(|| {
                 Flex::column()
-                    .with_child(Label::<RollShortcut>::dynamic(|shortcut, _env| format!("{}\n{}", shortcut.name, shortcut.roll)).with_line_break_mode(LineBreaking::WordWrap))
+                    .with_child(Label::<ShortcutItem>::dynamic(|ShortcutItem(shortcut), _env| format!("{}\n{}", shortcut.name, shortcut.roll)).with_line_break_mode(LineBreaking::WordWrap))
                     .with_child(
                         Flex::row()
-                            .with_child(Button::new("Roll").on_click(|ctx, shortcut: &mut RollShortcut, _env| ctx.submit_command(Command::new(Selector::new("ShortcutRoll"), shortcut.clone(), Target::Global))))
-                            .with_child(Button::new("Delete").on_click(|ctx, shortcut: &mut RollShortcut, _env| ctx.submit_command(Command::new(Selector::new("ShortcutDelete"), shortcut.clone(), Target::Global)))),
+                            .with_child(Button::new("Roll").on_click(|ctx, ShortcutItem(shortcut): &mut ShortcutItem, _env| ctx.submit_command(Command::new(Selector::new("ShortcutRoll"), shortcut.clone(), Target::Global))))
+                            .with_child(Button::new("Delete").on_click(|ctx, ShortcutItem(shortcut): &mut ShortcutItem, _env| ctx.submit_command(Command::new(Selector::new("ShortcutDelete"), shortcut.clone(), Target::Global)))),
                     )
             }).lens(DiceCalculator::shortcuts)
         ).vertical()
     )
 }
 
+fn build_system_presets() -> impl Widget<DiceCalculator> {
+    let mut presets = Flex::column().with_child(
+        Flex::row()
+            .with_child(Label::<DiceCalculator>::dynamic(|calc, _env| format!("Pool / modifier / skill: {}", calc.system_parameter)))
+            .with_child(Stepper::new().with_range(-10., 100.).with_step(1.).lens(DiceCalculator::system_parameter)),
+    );
+    for system in GameSystem::ALL.iter().copied() {
+        presets.add_child(Button::new(system.name()).on_click(move |_ctx, data: &mut DiceCalculator, _env| data.roll_system(system)));
+    }
+
+    Align::centered(Scroll::new(presets).vertical())
+}
+
 fn build_shortcuts_column() -> impl Widget<DiceCalculator> {
-    Split::rows(build_shortcut_creation_interface(), build_shortcut_list())
-        .split_point(0.15)
-        .solid_bar(true)
+    Split::rows(
+        build_shortcut_creation_interface(),
+        Split::rows(build_system_presets(), build_shortcut_list()).split_point(0.3).solid_bar(true),
+    )
+    .split_point(0.15)
+    .solid_bar(true)
 }
 
-fn build_main_window() -> impl Widget<DiceCalculator> {
+fn build_main_window(custom_dice: &[String]) -> impl Widget<DiceCalculator> {
     Split::columns(
-        Split::columns(build_shortcuts_column(), build_main_column()).split_point(1./3.).solid_bar(true).draggable(true),
+        Split::columns(build_shortcuts_column(), build_main_column(custom_dice)).split_point(1./3.).solid_bar(true).draggable(true),
         build_history_column(),
     )
     .split_point(0.75)
     .solid_bar(true)
     .draggable(true)
-    .controller(DiceCalcEventHandler {})
+    .controller(DiceCalcEventHandler { export_range: None })
+}
+
+fn build_export_menu<T: Data>() -> MenuDesc<T> {
+    let today = MenuItem::new(LocalizedString::new("Today's Rolls..."), EXPORT_HISTORY.with(ExportRange::Today));
+    let last_week = MenuItem::new(LocalizedString::new("The Last 7 Days..."), EXPORT_HISTORY.with(ExportRange::LastWeek));
+    let recent = MenuItem::new(LocalizedString::new("The Last 50 Rolls..."), EXPORT_HISTORY.with(ExportRange::Recent));
+    let all = MenuItem::new(LocalizedString::new("All Rolls..."), EXPORT_HISTORY.with(ExportRange::All));
+
+    MenuDesc::new(LocalizedString::new("Export History")).append(today).append(last_week).append(recent).append(all)
 }
 
 fn build_file_menu<T: Data>() -> MenuDesc<T> {
     let exit = MenuItem::new(LocalizedString::new("Exit"), QUIT_APP);
 
-    MenuDesc::new(LocalizedString::new("File")).append(exit)
+    MenuDesc::new(LocalizedString::new("File")).append(build_export_menu()).append_separator().append(exit)
 }
 
 fn build_menus<T: Data>() -> MenuDesc<T> {
@@ -561,11 +749,18 @@ fn build_menus<T: Data>() -> MenuDesc<T> {
 }
 
 fn main() {
-    let config = load_config();
-    let calculator = DiceCalculator::new(config);
+    let args: Args = argh::from_env();
+    let data_dir = DataDir::locate(args.data_dir).unwrap_or_else(|e| {
+        eprintln!("fluorite-gui: {}", e);
+        exit(1);
+    });
+    let mut problems = Vec::new();
+    let config = load_or_recover(&data_dir.config(), &mut problems);
+    let (dice, custom_dice) = load_dice(&data_dir, &mut problems);
+    let calculator = DiceCalculator::new(config, dice, data_dir, problems);
     save_config(&calculator);
 
-    let window = WindowDesc::new(build_main_window).title("Fluorite").menu(build_menus());
+    let window = WindowDesc::new(move || build_main_window(&custom_dice)).title("Fluorite").menu(build_menus());
 
     AppLauncher::with_window(window).launch(calculator).unwrap();
 }
@@ -1,43 +1,1086 @@
 use argh::FromArgs;
-use fluorite::parse::{clean_input, parse_input};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use fluorite::error::RollError;
+use fluorite::export::{csv_field, export, summarize_error, ExportFormat};
+use fluorite::limits::ResourceLimits;
+use fluorite::parse::{parse_with_limits, ArithmeticMode, CompiledRoll, CritMode, EvaluationContext, NaturalResult, RollInformation, RollValue};
+use fluorite::precision::{DisplayPolicy, Precision, RoundingMode};
+use fluorite::registry::{DiceDefinitions, DiceRegistry};
+use fluorite::session::{LogEntry, LogFilter, RngSource, SessionLog};
+use fluorite::storage::{DataDir, RollShortcut, Shortcuts};
+use fluorite::systems::GameSystem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal::prelude::*;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, write};
 use std::io::{stdin, Read};
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::Arc;
+
+////////////////
+//   Consts   //
+////////////////
+
+// Exit statuses, so scripts can tell what went wrong. Bad command-line arguments, including unknown shortcut names, exit with 1.
+const EXIT_USAGE_ERROR: i32 = 1;
+const EXIT_SYNTAX_ERROR: i32 = 2;
+const EXIT_EVALUATION_ERROR: i32 = 3;
+const EXIT_IO_ERROR: i32 = 4;
+
+const META_COMMANDS: [&str; 5] = [":verbose", ":seed", ":dist", ":help", ":quit"];
+const DISTRIBUTION_SAMPLES: u64 = 10_000;
+const DISTRIBUTION_ROWS: usize = 40;
+const DISTRIBUTION_BAR_WIDTH: u64 = 40;
+
+/////////////////
+//   Structs   //
+/////////////////
 
 /// Roll dice via string input.
 #[derive(FromArgs)]
+#[argh(error_code(2, "A roll wasn't a well-formed expression."), error_code(3, "A roll couldn't be evaluated, or went over a limit."), error_code(4, "A file or stdin couldn't be read."))]
 struct Args {
-    /// display full roll output
+    /// display full roll output; the same as --format verbose
     #[argh(switch, short = 'v')]
     verbose: bool,
+    /// how results are printed: "plain" (default), "verbose", "json" for one array of records, "ndjson" for one record per line, or "csv"
+    #[argh(option, default = "OutputFormat::Plain")]
+    format: OutputFormat,
+    /// stop at the first line which fails, rather than reporting it and carrying on
+    #[argh(switch)]
+    fail_fast: bool,
+    /// print each roll with constant arithmetic folded and like dice merged, instead of rolling it
+    #[argh(switch)]
+    simplify: bool,
+    /// roll every die group as a critical hit
+    #[argh(switch, short = 'c')]
+    crit: bool,
+    /// how critical dice are rolled: "double" (default) or "maximize"
+    #[argh(option, default = "CritMode::Double")]
+    crit_mode: CritMode,
+    /// how numbers are represented while evaluating: "decimal" (default) or "rational" for exact fractions
+    #[argh(option, default = "ArithmeticMode::Decimal")]
+    arithmetic: ArithmeticMode,
+    /// how results are rounded for display: "half-up" (default), "bankers", "floor", or "toward-zero"
+    #[argh(option, default = "RoundingMode::HalfUp")]
+    rounding: RoundingMode,
+    /// decimal places shown in results (default 4, so 10/3 shows as 3.3333), or "rational" to show fractions
+    #[argh(option, default = "Precision::Places(4)")]
+    precision: Precision,
+    /// directory to keep shortcuts, REPL history, and the session log in, instead of the usual per-user or portable location
+    #[argh(option)]
+    data_dir: Option<PathBuf>,
+    /// profile to record rolls under in the session log, such as the name of a character
+    #[argh(option)]
+    profile: Option<String>,
+    /// don't record rolls in the session log
+    #[argh(switch)]
+    no_log: bool,
+    /// JSON file of custom dice definitions to roll by name, instead of dice.json in the data directory
+    #[argh(option)]
+    dice_file: Option<PathBuf>,
+    /// most dice allowed in a single group, such as the 3 of 3d6 (default 10000)
+    #[argh(option)]
+    max_dice_per_group: Option<u64>,
+    /// most dice allowed across a whole roll (default 100000)
+    #[argh(option)]
+    max_total_dice: Option<u64>,
+    /// most levels of nested parentheses allowed (default 32)
+    #[argh(option)]
+    max_nesting_depth: Option<u64>,
+    /// most characters allowed in a roll (default 1000)
+    #[argh(option)]
+    max_input_length: Option<u64>,
+    /// most rerolls allowed in a chain of exploding dice (default 100)
+    #[argh(option)]
+    max_explosion_chain: Option<u64>,
+    /// show groups of more dice than this as a count of each face instead of listing every die (default 1000)
+    #[argh(option)]
+    summarize_above: Option<u64>,
+    #[argh(subcommand)]
+    command: Option<Command>,
     #[argh(positional)]
     roll: Vec<String>,
 }
 
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    System(SystemCommand),
+    Repl(ReplCommand),
+    Run(RunCommand),
+    Shortcuts(ShortcutsCommand),
+    Log(LogCommand),
+    Export(ExportCommand),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Plain,
+    Verbose,
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s.to_lowercase().as_str() {
+            "plain" => Ok(OutputFormat::Plain),
+            "verbose" => Ok(OutputFormat::Verbose),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("Unknown format \"{}\"; expected plain, verbose, json, ndjson, or csv.", s)),
+        }
+    }
+}
+
+/// What went wrong with one line, in machine-readable output.
+#[derive(Serialize)]
+struct ErrorRecord {
+    kind: &'static str,
+    message: String,
+}
+
+/// The outcome of one input line, in machine-readable output. Fields which don't apply are null.
+#[derive(Serialize)]
+struct OutputRecord {
+    input: String,
+    canonical: Option<String>,
+    /// The roll with constant arithmetic folded and like dice merged, when --simplify is given instead of rolling.
+    simplified: Option<String>,
+    trace: Option<String>,
+    value: Option<RollValue>,
+    /// The value rounded as it would be shown, following --precision and --rounding.
+    display: Option<String>,
+    outcome: Option<String>,
+    error: Option<ErrorRecord>,
+}
+
+impl OutputRecord {
+    fn new(input: &str, canonical: Option<String>, results: Result<RollInformation, RollError>, policy: &DisplayPolicy) -> OutputRecord {
+        let mut record = OutputRecord {
+            input: String::from(input),
+            canonical,
+            simplified: None,
+            trace: None,
+            value: None,
+            display: None,
+            outcome: None,
+            error: None,
+        };
+        match results {
+            Ok(results) => {
+                record.display = Some(policy.format_value(&results.value));
+                record.trace = Some(results.processed_string);
+                record.value = Some(results.value);
+                record.outcome = results.outcome;
+            }
+            Err(e) => {
+                record.error = Some(ErrorRecord {
+                    kind: e.kind(),
+                    message: e.to_string(),
+                })
+            }
+        }
+
+        record
+    }
+    fn simplified(input: &str, canonical: String, simplified: String) -> OutputRecord {
+        OutputRecord {
+            input: String::from(input),
+            canonical: Some(canonical),
+            simplified: Some(simplified),
+            trace: None,
+            value: None,
+            display: None,
+            outcome: None,
+            error: None,
+        }
+    }
+    fn to_csv(&self) -> String {
+        let fields = [
+            Some(self.input.clone()),
+            self.canonical.clone(),
+            self.simplified.clone(),
+            self.trace.clone(),
+            self.value.as_ref().map(|value| value.to_string()),
+            self.display.clone(),
+            self.outcome.clone(),
+            self.error.as_ref().map(|error| String::from(error.kind)),
+            self.error.as_ref().map(|error| error.message.clone()),
+        ];
+        fields.iter().map(|field| csv_field(field.as_deref().unwrap_or(""))).collect::<Vec<String>>().join(",")
+    }
+}
+
+/// Prints results in the chosen format, holding on to records if they have to be printed together at the end.
+struct Output<'a> {
+    format: OutputFormat,
+    policy: &'a DisplayPolicy,
+    records: Vec<OutputRecord>,
+    /// The exit status for the first line which failed, if any has.
+    exit_code: Option<i32>,
+}
+
+impl<'a> Output<'a> {
+    fn new(format: OutputFormat, policy: &'a DisplayPolicy) -> Output<'a> {
+        if format == OutputFormat::Csv {
+            println!("input,canonical,simplified,trace,value,display,outcome,error_kind,error_message");
+        }
+        Output {
+            format,
+            policy,
+            records: Vec::new(),
+            exit_code: None,
+        }
+    }
+    /// Prints one line's results, returning whether it succeeded.
+    fn emit(&mut self, input: &str, canonical: Option<String>, results: Result<RollInformation, RollError>) -> bool {
+        let succeeded = match &results {
+            Ok(_) => true,
+            Err(e) => {
+                self.exit_code = self.exit_code.or(Some(exit_code(e)));
+                false
+            }
+        };
+        match self.format {
+            OutputFormat::Plain | OutputFormat::Verbose => match results {
+                Ok(results) => print_results(canonical.as_deref().unwrap_or(input), &results, self.format == OutputFormat::Verbose, self.policy),
+                Err(e) => report_error(input, &e),
+            },
+            OutputFormat::Json => self.records.push(OutputRecord::new(input, canonical, results, self.policy)),
+            OutputFormat::Ndjson => println!("{}", serde_json::to_string(&OutputRecord::new(input, canonical, results, self.policy)).unwrap()),
+            OutputFormat::Csv => println!("{}", OutputRecord::new(input, canonical, results, self.policy).to_csv()),
+        }
+
+        succeeded
+    }
+    /// Prints one line's roll simplified, in place of its results.
+    fn emit_simplified(&mut self, input: &str, canonical: String, simplified: String) {
+        match self.format {
+            OutputFormat::Plain => println!("{}", simplified),
+            OutputFormat::Verbose => {
+                println!("Input: {}", canonical);
+                println!("Simplified: {}", simplified);
+            }
+            OutputFormat::Json => self.records.push(OutputRecord::simplified(input, canonical, simplified)),
+            OutputFormat::Ndjson => println!("{}", serde_json::to_string(&OutputRecord::simplified(input, canonical, simplified)).unwrap()),
+            OutputFormat::Csv => println!("{}", OutputRecord::simplified(input, canonical, simplified).to_csv()),
+        }
+    }
+    /// Prints anything still held, returning the exit status for the whole run.
+    fn finish(self) -> i32 {
+        if self.format == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&self.records).unwrap());
+        }
+        self.exit_code.unwrap_or(0)
+    }
+}
+
+/// Roll a game system's preset procedure (chronicles, shadowrun, blades, pbta, ironsworn, coc).
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "system",
+    example = "fluorite system blades 3\nfluorite system pbta --modifier -1",
+    note = "A negative parameter would be read as an option, so give it as --modifier -1, or after --, as in fluorite system pbta -- -1."
+)]
+struct SystemCommand {
+    #[argh(positional)]
+    system: GameSystem,
+    /// dice pool size for chronicles, shadowrun, and blades; stat modifier for pbta and ironsworn; skill for coc
+    #[argh(positional)]
+    parameter: Option<i64>,
+    /// stat modifier for pbta and ironsworn, instead of the positional parameter, so it can be negative: --modifier -1
+    #[argh(option, short = 'm')]
+    modifier: Option<i64>,
+}
+
+impl SystemCommand {
+    /// The parameter, whether it was given positionally or as --modifier, as long as it was given exactly once.
+    fn parameter_value(&self) -> Result<i64, String> {
+        match (self.parameter, self.modifier) {
+            (Some(parameter), None) | (None, Some(parameter)) => Ok(parameter),
+            (None, None) => Err(format!("{} needs a {}", self.system.name(), self.system.parameter_name())),
+            (Some(_), Some(_)) => Err(String::from("give the parameter either positionally or as --modifier, not both")),
+        }
+    }
+}
+
+/// Roll interactively, with line editing, history, and tab-completion of shortcut names.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "repl")]
+struct ReplCommand {}
+
+/// Roll a saved shortcut by name, such as "Longsword attack".
+#[derive(FromArgs)]
+#[argh(subcommand, name = "run")]
+struct RunCommand {
+    #[argh(positional)]
+    name: String,
+}
+
+/// List, add, delete, or rename the shortcuts shared with the GUI.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "shortcuts")]
+struct ShortcutsCommand {
+    #[argh(subcommand)]
+    action: ShortcutsAction,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum ShortcutsAction {
+    List(ListShortcuts),
+    Add(AddShortcut),
+    Delete(DeleteShortcut),
+    Rename(RenameShortcut),
+}
+
+/// Show every shortcut and its roll, newest first.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "list")]
+struct ListShortcuts {}
+
+/// Save a roll under a new name.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "add")]
+struct AddShortcut {
+    #[argh(positional)]
+    name: String,
+    #[argh(positional)]
+    roll: String,
+}
+
+/// Remove a shortcut.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "delete")]
+struct DeleteShortcut {
+    #[argh(positional)]
+    name: String,
+}
+
+/// Give a shortcut a new name.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "rename")]
+struct RenameShortcut {
+    #[argh(positional)]
+    name: String,
+    #[argh(positional)]
+    new_name: String,
+}
+
+/// Show or export past rolls from the session log, optionally only those within a range of dates.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "log")]
+struct LogCommand {
+    /// only rolls from this date on, as YYYY-MM-DD in local time, or from this RFC 3339 time on
+    #[argh(option, from_str_fn(parse_start_time))]
+    since: Option<DateTime<Utc>>,
+    /// only rolls up to the end of this date, as YYYY-MM-DD in local time, or from before this RFC 3339 time
+    #[argh(option, from_str_fn(parse_end_time))]
+    until: Option<DateTime<Utc>>,
+    /// only rolls made under this profile
+    #[argh(option)]
+    profile: Option<String>,
+    /// only the last this many rolls
+    #[argh(option)]
+    last: Option<usize>,
+}
+
+/// Export past rolls from the session log as a Markdown table, a standalone HTML page, or CSV, such as to paste into a campaign wiki.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "export")]
+struct ExportCommand {
+    /// the format to export to: "markdown" (default), "html", or "csv"
+    #[argh(option, default = "ExportFormat::Markdown")]
+    to: ExportFormat,
+    /// file to write the export to, instead of stdout
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+    /// only rolls from this date on, as YYYY-MM-DD in local time, or from this RFC 3339 time on
+    #[argh(option, from_str_fn(parse_start_time))]
+    since: Option<DateTime<Utc>>,
+    /// only rolls up to the end of this date, as YYYY-MM-DD in local time, or from before this RFC 3339 time
+    #[argh(option, from_str_fn(parse_end_time))]
+    until: Option<DateTime<Utc>>,
+    /// only rolls made under this profile
+    #[argh(option)]
+    profile: Option<String>,
+    /// only the last this many rolls
+    #[argh(option)]
+    last: Option<usize>,
+}
+
+/// Records rolls in the session log shared with the GUI, unless --no-log was given.
+struct Logger {
+    log: Option<SessionLog>,
+    profile: Option<String>,
+}
+
+impl Logger {
+    fn new(args: &Args) -> Logger {
+        let log = match args.no_log {
+            true => None,
+            false => match DataDir::locate(args.data_dir.clone()) {
+                Ok(data_dir) => Some(data_dir.session_log()),
+                Err(e) => {
+                    eprintln!("fluorite: not logging rolls: {}", e);
+                    None
+                }
+            },
+        };
+        Logger { log, profile: args.profile.clone() }
+    }
+    fn record(&self, input: String, results: &Result<RollInformation, RollError>, rng: Option<RngSource>, shortcut: Option<&str>) {
+        if let Some(log) = &self.log {
+            let entry = LogEntry {
+                profile: self.profile.clone(),
+                shortcut: shortcut.map(String::from),
+                ..LogEntry::new(input, results.clone().map_err(|e| e.to_string()), rng)
+            };
+            if let Err(e) = log.append(&entry) {
+                eprintln!("fluorite: failed to log a roll: {}", e);
+            }
+        }
+    }
+}
+
+/// Completes shortcut names and meta-commands at the start of a line.
+struct ReplHelper {
+    shortcuts: Vec<RollShortcut>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let prefix = &line[..pos];
+        let shortcut_names = self.shortcuts.iter().map(|shortcut| shortcut.name.as_str());
+        let candidates = META_COMMANDS.iter().copied().chain(shortcut_names).filter(|candidate| candidate.starts_with(prefix)).map(String::from).collect();
+
+        Ok((0, candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+struct Repl<'a> {
+    context: &'a EvaluationContext,
+    policy: &'a DisplayPolicy,
+    verbose: bool,
+    /// The generator for distributions, and for seeding each roll after :seed.
+    rng: StdRng,
+    /// The seed given with :seed, if rolls are following one; otherwise each roll gets a fresh seed of its own.
+    seed: Option<u64>,
+    logger: &'a Logger,
+}
+
+impl<'a> Repl<'a> {
+    /// Handles one line of input, returning false once it's time to quit.
+    fn handle_line(&mut self, line: &str, shortcuts: &[RollShortcut]) -> bool {
+        let mut words = line.splitn(2, ' ');
+        let (command, argument) = (words.next().unwrap_or(""), words.next().unwrap_or("").trim());
+        match command {
+            ":quit" | ":q" => return false,
+            ":help" => print_repl_help(),
+            ":verbose" => {
+                self.verbose = !self.verbose;
+                println!("Verbose output {}.", if self.verbose { "on" } else { "off" });
+            }
+            ":seed" if argument.is_empty() => {
+                self.rng = StdRng::from_entropy();
+                self.seed = None;
+                println!("Rolling with fresh randomness.");
+            }
+            ":seed" => match argument.parse::<u64>() {
+                Ok(seed) => {
+                    self.rng = StdRng::seed_from_u64(seed);
+                    self.seed = Some(seed);
+                    println!("Rolling with seed {}.", seed);
+                }
+                Err(_) => eprintln!("Seeds are whole numbers from 0 to {}.", u64::MAX),
+            },
+            ":dist" => match self.compile(argument, shortcuts).map_err(|e| e.to_string()).and_then(|roll| self.distribution(&roll)) {
+                Ok(()) => (),
+                Err(e) => eprintln!("{}", e),
+            },
+            _ if command.starts_with(':') => eprintln!("Unknown command \"{}\"; type :help for a list.", command),
+            _ => self.roll(line, shortcuts),
+        }
+
+        true
+    }
+
+    /// Rolls a line which isn't a command, then prints and logs the results.
+    fn roll(&mut self, line: &str, shortcuts: &[RollShortcut]) {
+        let shortcut = shortcuts.iter().find(|shortcut| shortcut.name == line).map(|shortcut| shortcut.name.as_str());
+        let roll = match self.compile(line, shortcuts) {
+            Ok(roll) => roll,
+            Err(e) => {
+                self.logger.record(String::from(line), &Err(e.clone()), None, shortcut);
+                eprintln!("{}", e);
+                return;
+            }
+        };
+
+        let (source, mut rng) = match self.seed {
+            Some(sequence) => RngSource::from_sequence(&mut self.rng, sequence),
+            None => RngSource::from_entropy(),
+        };
+        let results = roll.roll_with_rng(self.context, &mut rng);
+        self.logger.record(roll.to_string(), &results, Some(source), shortcut);
+        match results {
+            Ok(results) => print_results(&roll.to_string(), &results, self.verbose, self.policy),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    /// Compiles a roll, or the roll of the shortcut with that name.
+    fn compile(&self, input: &str, shortcuts: &[RollShortcut]) -> Result<CompiledRoll, RollError> {
+        let input = match shortcuts.iter().find(|shortcut| shortcut.name == input) {
+            Some(shortcut) => shortcut.roll.as_str(),
+            None => input,
+        };
+        parse_with_limits(input, &self.context.limits)
+    }
+
+    /// Estimates how likely each result of `roll` is by rolling it many times, and prints a histogram.
+    fn distribution(&mut self, roll: &CompiledRoll) -> Result<(), String> {
+        let mut frequencies: BTreeMap<Decimal, u64> = BTreeMap::new();
+        for _ in 0..DISTRIBUTION_SAMPLES {
+            let value = roll.roll_with_rng(self.context, &mut self.rng).and_then(|results| results.value.number()).map_err(|e| e.to_string())?;
+            *frequencies.entry(value).or_insert(0) += 1;
+        }
+        // Each result's share of the mean is no larger than the result, so adding up the shares can't overflow the way adding up the results could.
+        let mean = frequencies
+            .iter()
+            .try_fold(Decimal::from(0), |mean, (value, count)| mean.checked_add((*value / Decimal::from(DISTRIBUTION_SAMPLES)).checked_mul(Decimal::from(*count))?))
+            .ok_or_else(|| String::from("The results are too large to average."))?;
+
+        let (lowest, highest) = (*frequencies.keys().next().unwrap(), *frequencies.keys().next_back().unwrap());
+        let rows: Vec<(String, u64)> = if frequencies.len() <= DISTRIBUTION_ROWS {
+            frequencies.iter().map(|(value, count)| (self.policy.format_number(*value), *count)).collect()
+        } else {
+            // Too many distinct results to list, so group them into equal ranges, of whole numbers if every result is whole.
+            let too_far_apart = || String::from("The results are too far apart to group into ranges.");
+            let integers = frequencies.keys().all(|value| *value == value.floor());
+            let span = highest.checked_sub(lowest).ok_or_else(too_far_apart)?;
+            let width = if integers { (span.checked_add(Decimal::from(1)).ok_or_else(too_far_apart)? / Decimal::from(DISTRIBUTION_ROWS)).ceil() } else { span / Decimal::from(DISTRIBUTION_ROWS) };
+            let mut buckets = vec![0u64; DISTRIBUTION_ROWS];
+            for (value, count) in frequencies.iter() {
+                let offset = value.checked_sub(lowest).and_then(|offset| offset.checked_div(width)).ok_or_else(too_far_apart)?;
+                buckets[offset.floor().to_usize().unwrap_or(0).min(DISTRIBUTION_ROWS - 1)] += count;
+            }
+            let used = buckets.iter().rposition(|count| *count > 0).unwrap_or(0) + 1;
+            buckets
+                .into_iter()
+                .take(used)
+                .enumerate()
+                .map(|(index, count)| {
+                    let start = width.checked_mul(Decimal::from(index)).and_then(|offset| lowest.checked_add(offset)).ok_or_else(too_far_apart)?;
+                    let end = if integers { start.checked_add(width).and_then(|end| end.checked_sub(Decimal::from(1))) } else { start.checked_add(width) };
+                    Ok((format!("{} to {}", self.policy.format_number(start), self.policy.format_number(end.ok_or_else(too_far_apart)?)), count))
+                })
+                .collect::<Result<_, String>>()?
+        };
+
+        let label_width = rows.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+        let most = rows.iter().map(|(_, count)| *count).max().unwrap_or(1);
+        for (label, count) in rows {
+            let percent = Decimal::from(count * 100) / Decimal::from(DISTRIBUTION_SAMPLES);
+            let bar = "#".repeat((count * DISTRIBUTION_BAR_WIDTH / most) as usize);
+            println!("{:>width$}  {:>6.2}%  {}", label, percent, bar, width = label_width);
+        }
+        println!("Mean {} over {} rolls, from {} to {}.", self.policy.format_number(mean), DISTRIBUTION_SAMPLES, self.policy.format_number(lowest), self.policy.format_number(highest));
+
+        Ok(())
+    }
+}
+
+//////////////////////////
+//   Helper functions   //
+//////////////////////////
+
+/// The data directory shared with the GUI, reporting why and exiting if it can't be found.
+fn data_dir(explicit: &Option<PathBuf>) -> DataDir {
+    DataDir::locate(explicit.clone()).unwrap_or_else(|e| {
+        eprintln!("fluorite: {}", e);
+        exit(EXIT_IO_ERROR);
+    })
+}
+
+/// The built-in dice along with those from --dice-file, or from dice.json in the data directory if there is one.
+///
+/// A --dice-file which can't be loaded is an error, while a broken dice.json is reported and left out, so it doesn't stop ordinary rolls.
+fn load_dice(args: &Args) -> DiceRegistry {
+    let path = match &args.dice_file {
+        Some(path) => path.clone(),
+        None => match DataDir::locate(args.data_dir.clone()) {
+            Ok(data_dir) if data_dir.dice_path().exists() => data_dir.dice_path(),
+            _ => return DiceRegistry::new(),
+        },
+    };
+    let mut dice = DiceRegistry::new();
+    match DiceDefinitions::load(&path).and_then(|definitions| dice.register_definitions(definitions)) {
+        Ok(()) => dice,
+        Err(e) if args.dice_file.is_some() => {
+            eprintln!("fluorite: {}", e);
+            exit(EXIT_IO_ERROR);
+        }
+        Err(e) => {
+            eprintln!("fluorite: {}. Rolling with only the built-in dice.", e.trim_end_matches('.'));
+            DiceRegistry::new()
+        }
+    }
+}
+
+fn load_shortcuts(data_dir: &DataDir) -> Shortcuts {
+    data_dir.shortcuts().load().unwrap_or_default()
+}
+
+/// Finds the roll saved under `name`, exiting if there's no such shortcut.
+fn shortcut_roll(name: &str, data_dir: &DataDir) -> String {
+    let shortcuts = data_dir.shortcuts().load().unwrap_or_else(|e| {
+        eprintln!("fluorite: {}", e);
+        exit(EXIT_IO_ERROR);
+    });
+    match shortcuts.into_iter().find(|shortcut| shortcut.name == name) {
+        Some(shortcut) => shortcut.roll,
+        None => {
+            eprintln!("fluorite: there's no shortcut named \"{}\"", name);
+            exit(EXIT_USAGE_ERROR);
+        }
+    }
+}
+
+/// Carries out a `shortcuts` subcommand, returning the exit status.
+fn manage_shortcuts(action: &ShortcutsAction, data_dir: &DataDir, format: OutputFormat, limits: &ResourceLimits) -> i32 {
+    let store = data_dir.shortcuts();
+    let mut shortcuts = match store.load() {
+        Ok(shortcuts) => shortcuts,
+        Err(e) => {
+            eprintln!("fluorite: {}", e);
+            return EXIT_IO_ERROR;
+        }
+    };
+    let position = |shortcuts: &[RollShortcut], name: &str| shortcuts.iter().position(|shortcut| shortcut.name == name);
+
+    match action {
+        ShortcutsAction::List(_) => {
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&shortcuts).unwrap()),
+                OutputFormat::Ndjson => shortcuts.iter().for_each(|shortcut| println!("{}", serde_json::to_string(shortcut).unwrap())),
+                OutputFormat::Csv => {
+                    println!("name,roll");
+                    shortcuts.iter().for_each(|shortcut| println!("{},{}", csv_field(&shortcut.name), csv_field(&shortcut.roll)));
+                }
+                OutputFormat::Plain | OutputFormat::Verbose => {
+                    let name_width = shortcuts.iter().map(|shortcut| shortcut.name.chars().count()).max().unwrap_or(0);
+                    shortcuts.iter().for_each(|shortcut| println!("{:width$}  {}", shortcut.name, shortcut.roll, width = name_width));
+                }
+            }
+            return 0;
+        }
+        ShortcutsAction::Add(add) => {
+            if add.name.trim().is_empty() {
+                eprintln!("fluorite: a shortcut needs a name");
+                return EXIT_USAGE_ERROR;
+            }
+            if position(&shortcuts, &add.name).is_some() {
+                eprintln!("fluorite: there's already a shortcut named \"{}\"", add.name);
+                return EXIT_USAGE_ERROR;
+            }
+            let roll = match parse_with_limits(&add.roll, limits) {
+                Ok(roll) => roll,
+                Err(e) => {
+                    report_error(&add.roll, &e);
+                    return exit_code(&e);
+                }
+            };
+            // Newest first, as the GUI adds them.
+            shortcuts.insert(
+                0,
+                RollShortcut {
+                    name: add.name.clone(),
+                    roll: roll.to_string(),
+                },
+            );
+        }
+        ShortcutsAction::Delete(delete) => match position(&shortcuts, &delete.name) {
+            Some(index) => {
+                shortcuts.remove(index);
+            }
+            None => {
+                eprintln!("fluorite: there's no shortcut named \"{}\"", delete.name);
+                return EXIT_USAGE_ERROR;
+            }
+        },
+        ShortcutsAction::Rename(rename) => {
+            if rename.new_name.trim().is_empty() || position(&shortcuts, &rename.new_name).is_some() {
+                eprintln!("fluorite: can't rename to \"{}\", which is empty or already taken", rename.new_name);
+                return EXIT_USAGE_ERROR;
+            }
+            match position(&shortcuts, &rename.name) {
+                Some(index) => shortcuts[index].name = rename.new_name.clone(),
+                None => {
+                    eprintln!("fluorite: there's no shortcut named \"{}\"", rename.name);
+                    return EXIT_USAGE_ERROR;
+                }
+            }
+        }
+    }
+
+    match store.save(&shortcuts) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("fluorite: {}", e);
+            EXIT_IO_ERROR
+        }
+    }
+}
+
+fn print_repl_help() {
+    println!("Type a roll such as 2d6 + 3, or the name of a shortcut, and press Enter to roll it.");
+    println!(":verbose      toggle showing the input, dice, and result of each roll");
+    println!(":seed [n]     roll with a fixed seed, for repeatable results; with no number, go back to fresh randomness");
+    println!(":dist <roll>  roll {} times and show how often each result came up", DISTRIBUTION_SAMPLES);
+    println!(":help         show this list");
+    println!(":quit         leave (as does Ctrl-D)");
+}
+
+fn run_repl(context: &EvaluationContext, policy: &DisplayPolicy, verbose: bool, data_dir: &DataDir, logger: &Logger) {
+    let history_path = data_dir.repl_history_path();
+    let mut editor = Editor::<ReplHelper>::new();
+    editor.set_helper(Some(ReplHelper { shortcuts: load_shortcuts(data_dir) }));
+    let _ = editor.load_history(&history_path); // There's no history yet on the first run.
+
+    let mut repl = Repl {
+        context,
+        policy,
+        verbose,
+        rng: StdRng::from_entropy(),
+        seed: None,
+        logger,
+    };
+    println!("Type :help for help, or :quit to leave.");
+    loop {
+        match editor.readline("> ") {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                editor.add_history_entry(line);
+                let shortcuts = &editor.helper().unwrap().shortcuts;
+                if !repl.handle_line(line, shortcuts) {
+                    break;
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("Failed to read input: {}", e);
+                break;
+            }
+        }
+    }
+
+    let saved = create_dir_all(data_dir.data_path()).map_err(|e| e.to_string()).and_then(|_| editor.save_history(&history_path).map_err(|e| e.to_string()));
+    if let Err(e) = saved {
+        eprintln!("Failed to save history: {}", e);
+    }
+}
+
+/// Prints the session log entries which `command` asks for, returning the exit status.
+fn print_log(command: &LogCommand, data_dir: &DataDir, format: OutputFormat, policy: &DisplayPolicy) -> i32 {
+    let entries = match data_dir.session_log().query(&log_filter(command.since, command.until, &command.profile, command.last)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("fluorite: {}", e);
+            return EXIT_IO_ERROR;
+        }
+    };
+
+    match format {
+        OutputFormat::Plain => {
+            for entry in entries.iter() {
+                let result = match &entry.result {
+                    Ok(results) => match &results.outcome {
+                        Some(outcome) => format!("{} ({})", policy.format_value(&results.value), outcome),
+                        None => policy.format_value(&results.value),
+                    },
+                    Err(e) => format!("error: {}", summarize_error(e)),
+                };
+                println!("{}  {}  =  {}", local_time(&entry.time), entry.input, result);
+            }
+        }
+        OutputFormat::Verbose => {
+            for entry in entries.iter() {
+                println!("Time: {}", local_time(&entry.time));
+                if let Some(profile) = &entry.profile {
+                    println!("Profile: {}", profile);
+                }
+                if let Some(shortcut) = &entry.shortcut {
+                    println!("Shortcut: {}", shortcut);
+                }
+                println!("Input: {}", entry.input);
+                match &entry.result {
+                    Ok(results) => {
+                        println!("Rolled: {}", results.processed_string);
+                        println!("Result: {}", policy.format_value(&results.value));
+                        if let Some(outcome) = &results.outcome {
+                            println!("Outcome: {}", outcome);
+                        }
+                    }
+                    Err(e) => println!("Error: {}", e.trim()),
+                }
+                if let Some(rng) = &entry.rng {
+                    println!("Randomness: {}, seed {}", rng.name(), rng.seed());
+                }
+                println!();
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&entries).unwrap()),
+        OutputFormat::Ndjson => entries.iter().for_each(|entry| println!("{}", serde_json::to_string(entry).unwrap())),
+        OutputFormat::Csv => print!("{}", export_entries(&entries, ExportFormat::Csv, policy)),
+    }
+
+    0
+}
+
+fn export_log(command: &ExportCommand, data_dir: &DataDir, policy: &DisplayPolicy) -> i32 {
+    let entries = match data_dir.session_log().query(&log_filter(command.since, command.until, &command.profile, command.last)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("fluorite: {}", e);
+            return EXIT_IO_ERROR;
+        }
+    };
+    let document = export_entries(&entries, command.to, policy);
+
+    match &command.output {
+        Some(path) => match write(path, document) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("fluorite: Couldn't write {}: {}", path.display(), e);
+                EXIT_IO_ERROR
+            }
+        },
+        None => {
+            print!("{}", document);
+            0
+        }
+    }
+}
+
+fn local_time(time: &DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// The entries picked out by the --since, --until, --profile, and --last options, which `log` and `export` share.
+fn log_filter(since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>, profile: &Option<String>, last: Option<usize>) -> LogFilter {
+    LogFilter {
+        since,
+        until,
+        profile: profile.clone(),
+        last,
+    }
+}
+
+/// Renders the entries picked out for `log --format csv` or `export`, with times in local time as `log` prints them.
+fn export_entries(entries: &[LogEntry], format: ExportFormat, policy: &DisplayPolicy) -> String {
+    export(entries, format, policy, &Local)
+}
+
+/// Reads a date as the moment it starts in local time, or an RFC 3339 time as it is.
+fn parse_start_time(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_time(value, false)
+}
+
+/// Reads a date as the moment it ends in local time, or an RFC 3339 time as it is.
+fn parse_end_time(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_time(value, true)
+}
+
+fn parse_time(value: &str, end_of_day: bool) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| format!("Expected a date such as 2021-06-30 or a time such as 2021-06-30T18:00:00Z, not \"{}\".", value))?;
+    let date = if end_of_day { date.succ_opt().ok_or_else(|| String::from("That date is too late."))? } else { date };
+    match Local.from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap()).earliest() {
+        Some(time) => Ok(time.with_timezone(&Utc)),
+        None => Err(format!("Midnight on {} doesn't exist in the local time zone.", date)),
+    }
+}
+
+fn exit_code(error: &RollError) -> i32 {
+    match error {
+        RollError::Syntax(_) => EXIT_SYNTAX_ERROR,
+        RollError::Evaluation(_) | RollError::LimitExceeded { .. } => EXIT_EVALUATION_ERROR,
+    }
+}
+
+/// Explains on stderr why `input` couldn't be rolled, putting multi-line messages such as syntax errors on lines of their own.
+fn report_error(input: &str, error: &RollError) {
+    let message = error.to_string();
+    let separator = if message.contains('\n') { "\n" } else { " " };
+    eprintln!("fluorite: {} error in \"{}\":{}{}", error.kind(), input.trim(), separator, message.trim_end());
+}
+
+fn print_results(input: &str, results: &RollInformation, verbose: bool, policy: &DisplayPolicy) {
+    let value = policy.format_value(&results.value);
+    if verbose {
+        println!("Input: {}", input);
+        println!("Rolled: {}", results.processed_string);
+        println!("Result: {}", value);
+        if let Some(outcome) = &results.outcome {
+            println!("Outcome: {}", outcome);
+        }
+        if let Some(attack_die) = results.attack_die {
+            match attack_die.natural() {
+                Some(NaturalResult::CriticalHit) => println!("Natural {}: critical hit!", attack_die.face),
+                Some(NaturalResult::CriticalMiss) => println!("Natural {}: critical miss!", attack_die.face),
+                None => (),
+            }
+        }
+    } else {
+        match &results.outcome {
+            Some(outcome) => println!("{} ({})", value, outcome),
+            None => println!("{}", value),
+        }
+    }
+}
+
+fn resource_limits(args: &Args) -> ResourceLimits {
+    let defaults = ResourceLimits::new();
+    ResourceLimits {
+        max_dice_per_group: args.max_dice_per_group.unwrap_or(defaults.max_dice_per_group),
+        max_total_dice: args.max_total_dice.unwrap_or(defaults.max_total_dice),
+        max_nesting_depth: args.max_nesting_depth.unwrap_or(defaults.max_nesting_depth),
+        max_input_length: args.max_input_length.unwrap_or(defaults.max_input_length),
+        max_explosion_chain: args.max_explosion_chain.unwrap_or(defaults.max_explosion_chain),
+    }
+}
+
 fn main() {
     let args: Args = argh::from_env();
+    let limits = resource_limits(&args);
+    let policy = DisplayPolicy {
+        rounding: args.rounding,
+        precision: args.precision,
+    };
+
+    let format = if args.verbose && args.format == OutputFormat::Plain { OutputFormat::Verbose } else { args.format };
+
+    if let Some(Command::Shortcuts(shortcuts_command)) = &args.command {
+        exit(manage_shortcuts(&shortcuts_command.action, &data_dir(&args.data_dir), format, &limits));
+    }
+
+    if let Some(Command::Log(log_command)) = &args.command {
+        exit(print_log(log_command, &data_dir(&args.data_dir), format, &policy));
+    }
+
+    if let Some(Command::Export(export_command)) = &args.command {
+        exit(export_log(export_command, &data_dir(&args.data_dir), &policy));
+    }
+
+    let logger = Logger::new(&args);
 
-    let input = if !args.roll.is_empty() {
+    if let Some(Command::System(system_command)) = &args.command {
+        let mut output = Output::new(format, &policy);
+        let system = system_command.system;
+        let parameter = match system_command.parameter_value() {
+            Ok(parameter) => parameter,
+            Err(e) => {
+                eprintln!("fluorite: {}", e);
+                exit(EXIT_USAGE_ERROR);
+            }
+        };
+        let (rng_source, mut rng) = RngSource::from_entropy();
+        let results = system.roll_with_rng(parameter, &limits, &mut rng);
+        logger.record(format!("{} ({})", system.name(), parameter), &results, Some(rng_source), None);
+        output.emit(&format!("{} {}", system.name(), parameter), None, results);
+        exit(output.finish());
+    }
+
+    let dice = load_dice(&args);
+
+    let context = EvaluationContext {
+        critical: args.crit,
+        crit_mode: args.crit_mode,
+        dice: Arc::new(dice),
+        limits,
+        summarize_above: args.summarize_above.or(EvaluationContext::new().summarize_above),
+        arithmetic: args.arithmetic,
+        ..EvaluationContext::new()
+    };
+
+    if let Some(Command::Repl(_)) = args.command {
+        run_repl(&context, &policy, args.verbose, &data_dir(&args.data_dir), &logger);
+        return;
+    }
+
+    let shortcut = match &args.command {
+        Some(Command::Run(run_command)) => Some(run_command.name.as_str()),
+        _ => None,
+    };
+    let input = if let Some(Command::Run(run_command)) = &args.command {
+        shortcut_roll(&run_command.name, &data_dir(&args.data_dir))
+    } else if !args.roll.is_empty() {
         args.roll.join(" ")
     } else {
         let mut buffer = String::new();
-        match stdin().read_to_string(&mut buffer) {
-            Ok(_) => (),
-            Err(_) => println!("Failed to read from stdin."),
-        };
+        if let Err(e) = stdin().read_to_string(&mut buffer) {
+            eprintln!("fluorite: failed to read from stdin: {}", e);
+            exit(EXIT_IO_ERROR);
+        }
         buffer
     };
 
+    let mut output = Output::new(format, &policy);
     for line in input.split('\n').filter(|line| line != &"") {
-        match parse_input(&line) {
-            Ok(results) => {
-                if args.verbose {
-                    println!("Input: {}", clean_input(line));
-                    println!("Rolled: {}", results.processed_string);
-                    println!("Result: {}", results.value);
-                } else {
-                    println!("{}", results.value);
-                }
+        let succeeded = match parse_with_limits(line, &context.limits) {
+            Ok(roll) if args.simplify => {
+                output.emit_simplified(line, roll.to_string(), roll.simplified(&context).to_string());
+                true
+            }
+            Ok(roll) => {
+                let (rng_source, mut rng) = RngSource::from_entropy();
+                let results = roll.roll_with_rng(&context, &mut rng);
+                logger.record(roll.to_string(), &results, Some(rng_source), shortcut);
+                output.emit(line, Some(roll.to_string()), results)
             }
-            Err(e) => panic!("{}", e),
+            Err(e) => {
+                logger.record(String::from(line), &Err(e.clone()), None, shortcut);
+                output.emit(line, None, Err(e))
+            }
+        };
+        if !succeeded && args.fail_fast {
+            break;
         }
     }
+    exit(output.finish());
 }
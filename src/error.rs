@@ -0,0 +1,66 @@
+use crate::limits::Limit;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Why a roll couldn't be produced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RollError {
+    /// The input isn't a well-formed roll expression.
+    Syntax(String),
+    /// The input parsed, but asks for something impossible, such as a negative number of dice.
+    Evaluation(String),
+    /// Evaluating the input would go past one of the configured resource limits.
+    LimitExceeded { limit: Limit, maximum: u64 },
+}
+
+impl RollError {
+    /// A short machine-readable name for the kind of error: `syntax`, `evaluation`, or `limit`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RollError::Syntax(_) => "syntax",
+            RollError::Evaluation(_) => "evaluation",
+            RollError::LimitExceeded { .. } => "limit",
+        }
+    }
+}
+
+impl fmt::Display for RollError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RollError::Syntax(message) | RollError::Evaluation(message) => write!(f, "{}", message),
+            RollError::LimitExceeded { limit, maximum } => write!(f, "Limit exceeded: at most {} {} allowed.", maximum, limit),
+        }
+    }
+}
+
+impl Error for RollError {}
+
+impl From<String> for RollError {
+    fn from(message: String) -> RollError {
+        RollError::Evaluation(message)
+    }
+}
+
+/// Why saved data couldn't be read or written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StorageError {
+    /// The file couldn't be read or written at all.
+    Io { path: PathBuf, message: String },
+    /// The file was read, but doesn't hold valid data for its store. A copy of it was kept at `kept`.
+    Corrupt { path: PathBuf, message: String, kept: PathBuf },
+    /// The file was saved by a newer version of Fluorite, in a format this one can't read, so it was left untouched.
+    NewerVersion { path: PathBuf, version: u64, supported: u64 },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io { path, message } => write!(f, "Failed to access {}: {}", path.display(), message),
+            StorageError::Corrupt { path, message, kept } => write!(f, "{} is not valid ({}); a copy was kept at {}", path.display(), message, kept.display()),
+            StorageError::NewerVersion { path, version, supported } => write!(f, "{} is in format version {}, but this version of Fluorite only understands up to {}", path.display(), version, supported),
+        }
+    }
+}
+
+impl Error for StorageError {}
@@ -0,0 +1,234 @@
+//! Renders rolls from the session log as documents to paste or publish elsewhere, such as a campaign wiki.
+//!
+//! Every format includes each roll's time, input, trace of the dice rolled, and result or error. Markdown and HTML are for people, so they show times
+//! in the zone they're given and sum up syntax errors in a line; CSV is for spreadsheets and scripts, so it keeps every field of the log in full.
+
+use crate::precision::DisplayPolicy;
+use crate::session::LogEntry;
+use chrono::{SecondsFormat, TimeZone};
+use std::fmt::{Display, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const CSV_HEADER: &str = "time,profile,input,trace,value,display,outcome,error,rng_source,rng_seed,shortcut";
+
+const HTML_STYLE: &str = "
+    body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 60em; padding: 0 1em; color: #222; }
+    h1 { font-size: 1.5em; }
+    p.summary { color: #666; }
+    table { border-collapse: collapse; width: 100%; }
+    th, td { border-bottom: 1px solid #ddd; padding: 0.4em 0.6em; text-align: left; vertical-align: top; }
+    th { background: #f4f4f4; }
+    td.time { color: #666; white-space: nowrap; }
+    td.result { font-weight: bold; }
+    code, pre { font-family: ui-monospace, monospace; }
+    pre { margin: 0; white-space: pre-wrap; }
+    tr.error td.result { color: #c03030; font-weight: normal; }
+    .outcome { color: #666; font-weight: normal; }";
+
+/// A document format rolls can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A Markdown table.
+    Markdown,
+    /// A standalone, styled HTML page.
+    Html,
+    /// Comma-separated values, with the same columns as `fluorite log --format csv`.
+    Csv,
+}
+
+impl ExportFormat {
+    /// The usual file extension for the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Csv => "csv",
+        }
+    }
+
+    /// The format going by the extension of `path`, if it's one the format is known by.
+    pub fn from_path(path: &Path) -> Option<ExportFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "html" | "htm" => Some(ExportFormat::Html),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ExportFormat, String> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(format!("Unknown export format \"{}\"; expected markdown, html, or csv.", s)),
+        }
+    }
+}
+
+/// Renders `entries` in `format`, showing values as `policy` says and times in `zone`.
+pub fn export<Tz: TimeZone>(entries: &[LogEntry], format: ExportFormat, policy: &DisplayPolicy, zone: &Tz) -> String
+where
+    Tz::Offset: Display,
+{
+    match format {
+        ExportFormat::Markdown => to_markdown(entries, policy, zone),
+        ExportFormat::Html => to_html(entries, policy, zone),
+        ExportFormat::Csv => to_csv(entries, policy, zone),
+    }
+}
+
+/// The line of an error message which says what went wrong.
+///
+/// Syntax errors point out where the problem is over several lines, and end in the line which says what was expected.
+pub fn summarize_error(message: &str) -> &str {
+    message.lines().map(str::trim).rfind(|line| !line.is_empty()).unwrap_or("").trim_start_matches("= ")
+}
+
+/// Quotes a CSV field if it contains anything which would otherwise break the row apart.
+pub fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        String::from(text)
+    }
+}
+
+//////////////////////////
+//   Helper functions   //
+//////////////////////////
+
+fn to_markdown<Tz: TimeZone>(entries: &[LogEntry], policy: &DisplayPolicy, zone: &Tz) -> String
+where
+    Tz::Offset: Display,
+{
+    if entries.is_empty() {
+        return String::from("No rolls.\n");
+    }
+    let mut document = String::from("| Time | Input | Rolled | Result |\n| --- | --- | --- | --- |\n");
+    for entry in entries.iter() {
+        let (trace, result) = match &entry.result {
+            Ok(results) => {
+                let value = format!("**{}**", markdown_text(&policy.format_value(&results.value)));
+                let result = match &results.outcome {
+                    Some(outcome) => format!("{} ({})", value, markdown_text(outcome)),
+                    None => value,
+                };
+                (markdown_code(&results.processed_string), result)
+            }
+            Err(e) => (String::new(), format!("Error: {}", markdown_text(summarize_error(e)))),
+        };
+        let time = entry.time.with_timezone(zone).format(TIME_FORMAT);
+        writeln!(document, "| {} | {} | {} | {} |", time, markdown_code(&entry.input), trace, result).unwrap();
+    }
+
+    document
+}
+
+/// Escapes `text` for a Markdown table cell, so that it shows as written.
+fn markdown_text(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | '|' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Sets `text` as inline code in a Markdown table cell.
+fn markdown_code(text: &str) -> String {
+    if text.is_empty() || text.contains('`') {
+        return markdown_text(text);
+    }
+    // Pipes still end a table cell inside inline code unless they're escaped.
+    format!("`{}`", text.replace('|', "\\|").replace(['\n', '\r'], " "))
+}
+
+fn to_html<Tz: TimeZone>(entries: &[LogEntry], policy: &DisplayPolicy, zone: &Tz) -> String
+where
+    Tz::Offset: Display,
+{
+    let mut document = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Fluorite rolls</title>\n");
+    writeln!(document, "<style>{}\n</style>\n</head>\n<body>\n<h1>Fluorite rolls</h1>", HTML_STYLE).unwrap();
+    let (first, last) = match (entries.first(), entries.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+            document.push_str("<p class=\"summary\">No rolls.</p>\n</body>\n</html>\n");
+            return document;
+        }
+    };
+    let count = match entries.len() {
+        1 => String::from("1 roll"),
+        n => format!("{} rolls", n),
+    };
+    let span = (first.time.with_timezone(zone).format(TIME_FORMAT), last.time.with_timezone(zone).format(TIME_FORMAT));
+    writeln!(document, "<p class=\"summary\">{}, from {} to {}.</p>", count, span.0, span.1).unwrap();
+    document.push_str("<table>\n<thead><tr><th>Time</th><th>Input</th><th>Rolled</th><th>Result</th></tr></thead>\n<tbody>\n");
+    for entry in entries.iter() {
+        let time = entry.time.with_timezone(zone);
+        let time = format!("<time datetime=\"{}\">{}</time>", time.to_rfc3339_opts(SecondsFormat::Secs, true), time.format(TIME_FORMAT));
+        let input = format!("<code>{}</code>", html_text(&entry.input));
+        match &entry.result {
+            Ok(results) => {
+                let result = match &results.outcome {
+                    Some(outcome) => format!("{} <span class=\"outcome\">({})</span>", html_text(&policy.format_value(&results.value)), html_text(outcome)),
+                    None => html_text(&policy.format_value(&results.value)),
+                };
+                let trace = format!("<code>{}</code>", html_text(&results.processed_string));
+                writeln!(document, "<tr><td class=\"time\">{}</td><td>{}</td><td>{}</td><td class=\"result\">{}</td></tr>", time, input, trace, result).unwrap();
+            }
+            // The whole of a syntax error lines up with the input only in a monospace font which keeps its spacing.
+            Err(e) => writeln!(document, "<tr class=\"error\"><td class=\"time\">{}</td><td>{}</td><td></td><td class=\"result\"><pre>{}</pre></td></tr>", time, input, html_text(e.trim_end())).unwrap(),
+        }
+    }
+    document.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+
+    document
+}
+
+fn html_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
+
+fn to_csv<Tz: TimeZone>(entries: &[LogEntry], policy: &DisplayPolicy, zone: &Tz) -> String
+where
+    Tz::Offset: Display,
+{
+    let mut document = format!("{}\n", CSV_HEADER);
+    for entry in entries.iter() {
+        let (trace, value, display, outcome, error) = match &entry.result {
+            Ok(results) => (Some(results.processed_string.clone()), Some(results.value.to_string()), Some(policy.format_value(&results.value)), results.outcome.clone(), None),
+            Err(e) => (None, None, None, None, Some(e.clone())),
+        };
+        let fields = [
+            Some(entry.time.with_timezone(zone).to_rfc3339_opts(SecondsFormat::Millis, true)),
+            entry.profile.clone(),
+            Some(entry.input.clone()),
+            trace,
+            value,
+            display,
+            outcome,
+            error,
+            entry.rng.map(|rng| String::from(rng.name())),
+            entry.rng.map(|rng| rng.seed().to_string()),
+            entry.shortcut.clone(),
+        ];
+        writeln!(document, "{}", fields.iter().map(|field| csv_field(field.as_deref().unwrap_or(""))).collect::<Vec<String>>().join(",")).unwrap();
+    }
+
+    document
+}
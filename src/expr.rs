@@ -0,0 +1,141 @@
+use rust_decimal::prelude::*;
+use std::fmt;
+
+// Binding strength of each kind of expression, loosest first.
+pub(crate) const ADDITIVE: u8 = 1;
+pub(crate) const MULTIPLICATIVE: u8 = 2;
+pub(crate) const DICE: u8 = 3;
+pub(crate) const UNARY: u8 = 4;
+pub(crate) const ATOM: u8 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Plus,
+    Minus,
+    Times,
+    Divide,
+    Mod,
+}
+
+impl BinaryOperator {
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Plus | BinaryOperator::Minus => ADDITIVE,
+            BinaryOperator::Times | BinaryOperator::Divide | BinaryOperator::Mod => MULTIPLICATIVE,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryOperator::Plus => write!(f, "+"),
+            BinaryOperator::Minus => write!(f, "-"),
+            BinaryOperator::Times => write!(f, "*"),
+            BinaryOperator::Divide => write!(f, "/"),
+            BinaryOperator::Mod => write!(f, "%"),
+        }
+    }
+}
+
+/// Postfix operations on a dice pool, applied with the same precedence as dice.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PoolModifier {
+    Sort,
+    Unique,
+    Count(Decimal),
+    Matching,
+}
+
+impl fmt::Display for PoolModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolModifier::Sort => write!(f, "s"),
+            PoolModifier::Unique => write!(f, "u"),
+            PoolModifier::Count(face) => write!(f, "c{}", face.normalize()),
+            PoolModifier::Matching => write!(f, "m"),
+        }
+    }
+}
+
+/// A parsed roll expression, with precedence already resolved into the shape of the tree.
+///
+/// Displaying an expression gives its canonical form: minimal parentheses, spaces around arithmetic operators, and none around dice.
+/// Parsing the canonical form of a parsed expression gives back the same expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiceExpr {
+    Number(Decimal),
+    /// Negation of anything but a number, since a minus sign written before a number is parsed as part of it.
+    Negate(Box<DiceExpr>),
+    Binary(BinaryOperator, Box<DiceExpr>, Box<DiceExpr>),
+    /// A number of dice with a number of sides, as in `2d6`.
+    Dice(Box<DiceExpr>, Box<DiceExpr>),
+    /// A number of dice from the registry, as in `2dability`.
+    NamedDice(Box<DiceExpr>, String),
+    Modified(Box<DiceExpr>, PoolModifier),
+    Crit(Box<DiceExpr>),
+    /// A Call of Cthulhu percentile roll against a skill, with bonus dice if positive and penalty dice if negative.
+    Percentile(Box<DiceExpr>, i64),
+}
+
+impl DiceExpr {
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            DiceExpr::Number(number) if number.is_sign_negative() => UNARY,
+            DiceExpr::Number(_) | DiceExpr::Crit(_) | DiceExpr::Percentile(_, _) => ATOM,
+            DiceExpr::Negate(_) => UNARY,
+            DiceExpr::Binary(operator, _, _) => operator.precedence(),
+            DiceExpr::Dice(_, _) | DiceExpr::NamedDice(_, _) | DiceExpr::Modified(_, _) => DICE,
+        }
+    }
+}
+
+/// Wraps `text`, the rendering of `expression`, in parentheses if the expression binds more loosely than `minimum`.
+pub(crate) fn parenthesize(text: String, expression: &DiceExpr, minimum: u8) -> String {
+    if expression.precedence() < minimum {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+/// The operand of a postfix letter, parenthesized if it would otherwise run into the letter and be read as part of a die name.
+pub(crate) fn postfix_operand(text: String, expression: &DiceExpr) -> String {
+    let text = parenthesize(text, expression, DICE);
+    if text.ends_with(|c: char| c.is_ascii_alphabetic()) {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+pub(crate) fn format_percentile_modifier(extra_tens_dice: i64) -> String {
+    match extra_tens_dice {
+        0 => String::new(),
+        1 => String::from("b"),
+        -1 => String::from("p"),
+        bonus if bonus > 0 => format!("b{}", bonus),
+        penalty => format!("p{}", penalty.unsigned_abs()),
+    }
+}
+
+impl fmt::Display for DiceExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiceExpr::Number(number) => write!(f, "{}", number.normalize()),
+            DiceExpr::Negate(operand) => write!(f, "-{}", parenthesize(operand.to_string(), operand, ATOM)),
+            DiceExpr::Binary(operator, left, right) => write!(
+                f,
+                "{} {} {}",
+                parenthesize(left.to_string(), left, operator.precedence()),
+                operator,
+                parenthesize(right.to_string(), right, operator.precedence() + 1)
+            ),
+            DiceExpr::Dice(number, sides) => write!(f, "{}d{}", postfix_operand(number.to_string(), number), parenthesize(sides.to_string(), sides, UNARY)),
+            DiceExpr::NamedDice(number, name) => write!(f, "{}d{}", postfix_operand(number.to_string(), number), name),
+            DiceExpr::Modified(pool, modifier) => write!(f, "{}{}", postfix_operand(pool.to_string(), pool), modifier),
+            DiceExpr::Crit(inner) => write!(f, "crit({})", inner),
+            DiceExpr::Percentile(skill, extra_tens_dice) => write!(f, "coc({}){}", skill, format_percentile_modifier(*extra_tens_dice)),
+        }
+    }
+}
@@ -1 +1,13 @@
+pub mod error;
+pub mod export;
+pub mod expr;
+pub mod limits;
 pub mod parse;
+pub mod precision;
+pub mod rational;
+pub mod registry;
+pub mod session;
+pub mod simplify;
+pub mod storage;
+pub mod symbols;
+pub mod systems;
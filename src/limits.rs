@@ -0,0 +1,106 @@
+use crate::error::RollError;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fmt;
+
+/// A resource limit which a roll can exceed.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum Limit {
+    DicePerGroup,
+    TotalDice,
+    NestingDepth,
+    InputLength,
+    ExplosionChain,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::DicePerGroup => write!(f, "dice per group"),
+            Limit::TotalDice => write!(f, "dice in total"),
+            Limit::NestingDepth => write!(f, "levels of nested parentheses"),
+            Limit::InputLength => write!(f, "characters of input"),
+            Limit::ExplosionChain => write!(f, "rerolls in a chain of exploding dice"),
+        }
+    }
+}
+
+/// Caps on how much work a single roll may do, so a mistyped `999999999999d6` fails quickly instead of hanging.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default)]
+pub struct ResourceLimits {
+    /// Only applies to groups which keep every die; summarized groups count towards `max_total_dice` alone.
+    pub max_dice_per_group: u64,
+    pub max_total_dice: u64,
+    pub max_nesting_depth: u64,
+    pub max_input_length: u64,
+    pub max_explosion_chain: u64,
+}
+
+impl ResourceLimits {
+    pub fn new() -> ResourceLimits {
+        ResourceLimits {
+            max_dice_per_group: 10_000,
+            max_total_dice: 100_000,
+            max_nesting_depth: 32,
+            max_input_length: 1_000,
+            max_explosion_chain: 100,
+        }
+    }
+    pub fn maximum(&self, limit: Limit) -> u64 {
+        match limit {
+            Limit::DicePerGroup => self.max_dice_per_group,
+            Limit::TotalDice => self.max_total_dice,
+            Limit::NestingDepth => self.max_nesting_depth,
+            Limit::InputLength => self.max_input_length,
+            Limit::ExplosionChain => self.max_explosion_chain,
+        }
+    }
+    /// Fails with [`RollError::LimitExceeded`] if `amount` is over the given limit.
+    pub fn check(&self, limit: Limit, amount: u64) -> Result<(), RollError> {
+        let maximum = self.maximum(limit);
+        if amount > maximum {
+            Err(RollError::LimitExceeded { limit, maximum })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for ResourceLimits {
+    fn default() -> ResourceLimits {
+        ResourceLimits::new()
+    }
+}
+
+/// A running count of the dice rolled so far while evaluating one roll.
+pub(crate) struct DiceTally<'a> {
+    limits: &'a ResourceLimits,
+    rolled: Cell<u64>,
+}
+
+impl<'a> DiceTally<'a> {
+    pub(crate) fn new(limits: &'a ResourceLimits) -> DiceTally<'a> {
+        DiceTally { limits, rolled: Cell::new(0) }
+    }
+    pub(crate) fn limits(&self) -> &ResourceLimits {
+        self.limits
+    }
+    /// Records a group of `count` dice about to be rolled, failing before any are rolled if that would break a limit.
+    pub(crate) fn add(&self, count: u64) -> Result<(), RollError> {
+        self.limits.check(Limit::DicePerGroup, count)?;
+        let rolled = self.rolled.get().saturating_add(count);
+        self.limits.check(Limit::TotalDice, rolled)?;
+        self.rolled.set(rolled);
+
+        Ok(())
+    }
+    /// Records a group of `count` dice which will be summarized rather than kept, so only the total limit applies.
+    pub(crate) fn add_summarized(&self, count: u64) -> Result<(), RollError> {
+        let rolled = self.rolled.get().saturating_add(count);
+        self.limits.check(Limit::TotalDice, rolled)?;
+        self.rolled.set(rolled);
+
+        Ok(())
+    }
+}
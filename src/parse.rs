@@ -1,11 +1,23 @@
+use crate::error::RollError;
+use crate::expr::{format_percentile_modifier, parenthesize, BinaryOperator, DiceExpr, PoolModifier, ATOM};
+use crate::limits::{DiceTally, Limit, ResourceLimits};
+use crate::rational::Rational;
+use crate::registry::{DiceRegistry, DieFace};
+use crate::simplify::simplify;
+use crate::symbols::SymbolPool;
+use crate::systems::roll_percentile;
 use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use pest_derive::Parser;
-use rand::Rng;
+use rand::{Rng, RngCore};
 use rust_decimal::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
 
-pub const VALID_INPUT_CHARS: &str = "0123456789d.+-*/%() ";
+pub const VALID_INPUT_CHARS: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.+-*/%() ";
 
 #[derive(Clone)]
 enum Binop {
@@ -22,22 +34,284 @@ enum Unop {
     Minus,
 }
 
+/// How dice inside a critical sub-expression are rolled.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub enum CritMode {
+    /// Roll twice as many dice as written.
+    #[default]
+    Double,
+    /// Roll the dice as written, then add the maximum possible result of the same dice.
+    Maximize,
+}
+
+impl FromStr for CritMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CritMode, String> {
+        match s.to_lowercase().as_str() {
+            "double" => Ok(CritMode::Double),
+            "maximize" | "max" => Ok(CritMode::Maximize),
+            _ => Err(format!("Unknown crit mode \"{}\"; expected \"double\" or \"maximize\".", s)),
+        }
+    }
+}
+
+/// How numbers written in the input are represented while evaluating.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub enum ArithmeticMode {
+    /// Fixed-point decimals with 28 digits of precision, so `(1/3)*3` is 0.9999999999999999999999999999.
+    #[default]
+    Decimal,
+    /// Exact fractions, so `(1/3)*3` is 1. Converted to decimals only when a value is used as a dice count or shown.
+    Rational,
+}
+
+impl FromStr for ArithmeticMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ArithmeticMode, String> {
+        match s.to_lowercase().as_str() {
+            "decimal" => Ok(ArithmeticMode::Decimal),
+            "rational" | "exact" => Ok(ArithmeticMode::Rational),
+            _ => Err(format!("Unknown arithmetic mode \"{}\"; expected \"decimal\" or \"rational\".", s)),
+        }
+    }
+}
+
+/// Settings which affect how a roll is evaluated.
+#[derive(Clone, Debug)]
+pub struct EvaluationContext {
+    /// Whether every die group in the expression is rolled as a critical, as if the whole input were wrapped in `crit(...)`.
+    pub critical: bool,
+    pub crit_mode: CritMode,
+    /// Sides of the attack die; the first unmodified single die of this size is checked for natural minimum and maximum results.
+    pub attack_die_sides: Option<i128>,
+    /// Dice which can be rolled by name, as in `2dability`.
+    pub dice: Arc<DiceRegistry>,
+    pub limits: ResourceLimits,
+    /// Groups of more dice than this are rolled without keeping each die, and shown as a count of each face instead.
+    pub summarize_above: Option<u64>,
+    pub arithmetic: ArithmeticMode,
+}
+
+impl EvaluationContext {
+    pub fn new() -> EvaluationContext {
+        EvaluationContext {
+            critical: false,
+            crit_mode: CritMode::Double,
+            attack_die_sides: Some(20),
+            dice: Arc::new(DiceRegistry::new()),
+            limits: ResourceLimits::new(),
+            summarize_above: Some(1_000),
+            arithmetic: ArithmeticMode::Decimal,
+        }
+    }
+}
+
+impl Default for EvaluationContext {
+    fn default() -> EvaluationContext {
+        EvaluationContext::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum NaturalResult {
+    CriticalHit,
+    CriticalMiss,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct AttackDie {
+    pub face: Decimal,
+    pub sides: Decimal,
+}
+
+impl AttackDie {
+    pub fn natural(&self) -> Option<NaturalResult> {
+        if self.face == self.sides {
+            Some(NaturalResult::CriticalHit)
+        } else if self.face == Decimal::from(1) {
+            Some(NaturalResult::CriticalMiss)
+        } else {
+            None
+        }
+    }
+}
+
+/// The result of a roll: a plain number, an exact fraction from rational arithmetic, or a tally of narrative symbols.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum RollValue {
+    Number(Decimal),
+    Rational(Rational),
+    Symbols(SymbolPool),
+}
+
+impl RollValue {
+    pub fn number(&self) -> Result<Decimal, RollError> {
+        match self {
+            RollValue::Number(number) => Ok(*number),
+            RollValue::Rational(rational) => rational.to_decimal().ok_or_else(|| overflow_error(&rational.to_string())),
+            RollValue::Symbols(_) => Err(RollError::Evaluation(String::from("Attempted to use symbol dice as a number."))),
+        }
+    }
+    pub fn rational(&self) -> Result<Rational, RollError> {
+        match self {
+            RollValue::Number(number) => Rational::from_decimal(*number).ok_or_else(|| overflow_error(&number.to_string())),
+            RollValue::Rational(rational) => Ok(*rational),
+            RollValue::Symbols(_) => Err(RollError::Evaluation(String::from("Attempted to use symbol dice as a number."))),
+        }
+    }
+}
+
+impl From<Decimal> for RollValue {
+    fn from(number: Decimal) -> RollValue {
+        RollValue::Number(number)
+    }
+}
+
+impl fmt::Display for RollValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RollValue::Number(number) => write!(f, "{}", number),
+            RollValue::Rational(rational) => write!(f, "{}", rational),
+            RollValue::Symbols(symbols) => write!(f, "{}", symbols),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct RollInformation {
-    pub value: Decimal,
+    pub value: RollValue,
     pub processed_string: String,
+    #[serde(default)]
+    pub attack_die: Option<AttackDie>,
+    /// The individual dice making up the value, if it's a dice pool which hasn't yet been summed by an arithmetic operator.
+    #[serde(default)]
+    pub pool: Option<Vec<Decimal>>,
+    /// How many times each face came up, if it's a dice group too large to list which hasn't yet been summed by an arithmetic operator.
+    #[serde(default)]
+    pub frequencies: Option<BTreeMap<Decimal, u64>>,
+    /// A system-specific reading of the result, such as "Partial success".
+    #[serde(default)]
+    pub outcome: Option<String>,
 }
 
 impl RollInformation {
-    fn new(value: Decimal, processed_string: String) -> RollInformation {
-        RollInformation { value, processed_string }
+    pub(crate) fn new(value: impl Into<RollValue>, processed_string: String) -> RollInformation {
+        RollInformation {
+            value: value.into(),
+            processed_string,
+            attack_die: None,
+            pool: None,
+            frequencies: None,
+            outcome: None,
+        }
+    }
+    fn new_pool(pool: Vec<Decimal>, processed_string: String) -> Result<RollInformation, RollError> {
+        let value = checked_sum(pool.iter().copied()).ok_or_else(|| overflow_error(&processed_string))?;
+        Ok(RollInformation {
+            value: RollValue::Number(value),
+            processed_string,
+            attack_die: None,
+            pool: Some(pool),
+            frequencies: None,
+            outcome: None,
+        })
+    }
+    fn new_summary(frequencies: BTreeMap<Decimal, u64>, processed_string: String) -> Result<RollInformation, RollError> {
+        let value = summary_sum(&frequencies).ok_or_else(|| overflow_error(&processed_string))?;
+        Ok(RollInformation {
+            value: RollValue::Number(value),
+            processed_string,
+            attack_die: None,
+            pool: None,
+            frequencies: Some(frequencies),
+            outcome: None,
+        })
+    }
+    fn with_attack_die(mut self, attack_die: Option<AttackDie>) -> RollInformation {
+        self.attack_die = attack_die;
+        self
+    }
+    pub(crate) fn with_outcome(mut self, outcome: &str) -> RollInformation {
+        self.outcome = Some(String::from(outcome));
+        self
+    }
+    pub fn natural(&self) -> Option<NaturalResult> {
+        self.attack_die.and_then(|die| die.natural())
     }
 }
 
-#[derive(Clone)]
-enum BinopSequenceMember {
-    NonBinop(RollInformation),
+enum SequenceMember {
+    Expr(DiceExpr),
     Binop(Binop),
+    Modifier(PoolModifier),
+    /// The name of a registered die, which is only meaningful on the right-hand side of a dice operator.
+    DieName(String),
+}
+
+/// A roll expression parsed once, which can then be rolled any number of times without parsing it again.
+///
+/// Displays as its canonical form, and serializes as that same string.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CompiledRoll {
+    expression: DiceExpr,
+}
+
+impl CompiledRoll {
+    pub fn new(expression: DiceExpr) -> CompiledRoll {
+        CompiledRoll { expression }
+    }
+    pub fn expression(&self) -> &DiceExpr {
+        &self.expression
+    }
+    pub fn roll(&self, context: &EvaluationContext) -> Result<RollInformation, RollError> {
+        self.roll_with_rng(context, &mut rand::thread_rng())
+    }
+    /// The same roll with constant arithmetic folded and like dice merged; see [`simplify`].
+    pub fn simplified(&self, context: &EvaluationContext) -> CompiledRoll {
+        CompiledRoll::new(simplify(&self.expression, context))
+    }
+    /// Rolls with the given random number generator, so a seeded generator gives the same results every time.
+    pub fn roll_with_rng<R: RngCore>(&self, context: &EvaluationContext, rng: &mut R) -> Result<RollInformation, RollError> {
+        let tally = DiceTally::new(&context.limits);
+        let mut information = evaluate(&self.expression, context, &tally, rng)?;
+        if let RollValue::Symbols(symbols) = &information.value {
+            information.value = RollValue::Symbols(symbols.cancelled(&context.dice.cancellations));
+        }
+
+        Ok(information)
+    }
+}
+
+impl fmt::Display for CompiledRoll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl FromStr for CompiledRoll {
+    type Err = RollError;
+
+    fn from_str(s: &str) -> Result<CompiledRoll, RollError> {
+        parse(s)
+    }
+}
+
+impl TryFrom<String> for CompiledRoll {
+    type Error = RollError;
+
+    fn try_from(s: String) -> Result<CompiledRoll, RollError> {
+        parse(&s)
+    }
+}
+
+impl From<CompiledRoll> for String {
+    fn from(roll: CompiledRoll) -> String {
+        roll.to_string()
+    }
 }
 
 #[derive(Parser)]
@@ -48,188 +322,350 @@ struct DiceParser;
 //   Helper functions   //
 //////////////////////////
 
-fn roll_die(sides: i128) -> Decimal {
-    let roll = rand::thread_rng().gen_range(1..=sides);
+fn overflow_error(expression: &str) -> RollError {
+    RollError::Evaluation(format!("The result of {} is too large to represent.", expression))
+}
+
+/// Applies a checked arithmetic operation, exactly if either operand is an exact fraction and as decimals otherwise.
+fn apply_arithmetic(
+    value1: &RollValue,
+    value2: &RollValue,
+    decimal_operation: impl Fn(Decimal, Decimal) -> Option<Decimal>,
+    rational_operation: impl Fn(Rational, Rational) -> Option<Rational>,
+    processed_string: &str,
+) -> Result<RollValue, RollError> {
+    match (value1, value2) {
+        (RollValue::Rational(_), _) | (_, RollValue::Rational(_)) => {
+            let result = rational_operation(value1.rational()?, value2.rational()?).ok_or_else(|| overflow_error(processed_string))?;
+            Ok(RollValue::Rational(result))
+        }
+        _ => {
+            let result = decimal_operation(value1.number()?, value2.number()?).ok_or_else(|| overflow_error(processed_string))?;
+            Ok(RollValue::Number(result))
+        }
+    }
+}
+
+fn checked_sum(mut values: impl Iterator<Item = Decimal>) -> Option<Decimal> {
+    values.try_fold(Decimal::from(0), |sum, value| sum.checked_add(value))
+}
+
+pub(crate) fn roll_die(sides: i128, rng: &mut dyn RngCore) -> Decimal {
+    let roll = rng.gen_range(1..=sides);
     Decimal::from(roll)
 }
 
-fn roll_dice(number: Decimal, sides: Decimal) -> Result<(Decimal, Vec<Decimal>), String> {
+/// Validates a dice group, converting its count and sides to integers by value rather than by representation, so `2.0d6.00` is the same as `2d6`.
+fn check_dice(number: Decimal, sides: Decimal) -> Result<(u64, i128), RollError> {
     if number != number.floor() {
-        return Err(String::from("Attempted to roll non-integer number of dice."));
+        return Err(RollError::Evaluation(String::from("Attempted to roll non-integer number of dice.")));
     } else if sides != sides.floor() {
-        return Err(String::from("Attempted to roll dice with non-integer number of sides."));
+        return Err(RollError::Evaluation(String::from("Attempted to roll dice with non-integer number of sides.")));
     } else if number.is_sign_negative() {
-        return Err(String::from("Attempted to roll negative number of dice."));
-    } else if !sides.is_sign_positive() {
-        return Err(String::from("Attempted to roll dice with non-positive number of sides."));
-    } else if number.is_zero() {
-        return Ok((Decimal::from(0), vec![Decimal::from(0)]));
+        return Err(RollError::Evaluation(String::from("Attempted to roll negative number of dice.")));
+    } else if sides <= Decimal::from(0) {
+        return Err(RollError::Evaluation(String::from("Attempted to roll dice with non-positive number of sides.")));
     }
 
-    let number_as_int = number.abs().mantissa();
-    let sides_as_int = sides.abs().mantissa();
+    match (number.normalize().to_u64(), sides.normalize().to_i128()) {
+        (Some(number_as_int), Some(sides_as_int)) => Ok((number_as_int, sides_as_int)),
+        (None, _) => Err(RollError::Evaluation(format!("Attempted to roll {} dice, which is more than can be counted.", number))),
+        (_, None) => Err(RollError::Evaluation(format!("Attempted to roll dice with {} sides, which is more than can be counted.", sides))),
+    }
+}
 
-    let mut sum = Decimal::from(0);
-    let mut rolls = Vec::new();
-    for _ in 0..number_as_int {
-        let roll = roll_die(sides_as_int);
-        sum += roll;
-        rolls.push(roll);
+fn roll_dice(number: Decimal, sides: Decimal, tally: &DiceTally, rng: &mut dyn RngCore) -> Result<(Decimal, Vec<Decimal>), RollError> {
+    let (number_as_int, sides_as_int) = check_dice(number, sides)?;
+    if number_as_int == 0 {
+        return Ok((Decimal::from(0), Vec::new()));
     }
+    tally.add(number_as_int)?;
+
+    let rolls: Vec<Decimal> = (0..number_as_int).map(|_| roll_die(sides_as_int, rng)).collect();
+    let sum = checked_sum(rolls.iter().copied()).ok_or_else(|| overflow_error(&format_rolls(&rolls)))?;
 
     Ok((sum, rolls))
 }
 
-fn handle_binop_sequence_dice(mut sequence: Vec<BinopSequenceMember>) -> Result<Vec<BinopSequenceMember>, String> {
-    let mut next_dice_check = sequence.iter().position(|member| match member {
-        BinopSequenceMember::Binop(Binop::Dice) => true,
-        _ => false,
-    });
+/// Rolls a group of dice too large to list, keeping only how many times each face came up.
+fn roll_dice_summarized(number: Decimal, sides: Decimal, context: &EvaluationContext, tally: &DiceTally, rng: &mut dyn RngCore) -> Result<RollInformation, RollError> {
+    let (number_as_int, sides_as_int) = check_dice(number, sides)?;
+    let doubled = context.critical && context.crit_mode == CritMode::Double;
+    let dice_to_roll = if doubled { number_as_int.saturating_mul(2) } else { number_as_int };
+    tally.add_summarized(dice_to_roll)?;
 
-    while next_dice_check != None {
-        let next_dice_position = next_dice_check.unwrap();
-        let mut new_sequence = Vec::new();
+    let mut frequencies = BTreeMap::new();
+    for _ in 0..dice_to_roll {
+        *frequencies.entry(roll_die(sides_as_int, rng)).or_insert(0) += 1;
+    }
+    let mut processed_string = format_summary(&frequencies)?;
 
-        new_sequence.append(&mut sequence[..next_dice_position - 1].to_vec());
-        match (sequence[next_dice_position - 1].clone(), sequence[next_dice_position + 1].clone()) {
-            (BinopSequenceMember::NonBinop(info1), BinopSequenceMember::NonBinop(info2)) => {
-                let (value, new_rolls) = roll_dice(info1.value, info2.value)?;
+    if context.critical && context.crit_mode == CritMode::Maximize {
+        let maximized: BTreeMap<Decimal, u64> = std::iter::once((Decimal::from(sides_as_int), number_as_int)).collect();
+        processed_string = format!("{} + {}", processed_string, format_summary(&maximized)?);
+        *frequencies.entry(Decimal::from(sides_as_int)).or_insert(0) += number_as_int;
+    }
 
-                let processed_string = format!("[{}]", new_rolls.iter().map(|dec| dec.to_string()).collect::<Vec<String>>().join(", "));
+    RollInformation::new_summary(frequencies, processed_string)
+}
 
-                new_sequence.push(BinopSequenceMember::NonBinop(RollInformation::new(value, processed_string)));
+/// Rolls named dice from the registry. Crit mode doesn't apply to these, since their faces needn't have a meaningful maximum to double or maximize.
+fn roll_named_dice(number: Decimal, name: &str, context: &EvaluationContext, tally: &DiceTally, rng: &mut dyn RngCore) -> Result<RollInformation, RollError> {
+    let die = match context.dice.get(name) {
+        Some(die) => die,
+        None => return Err(RollError::Evaluation(format!("Attempted to roll unknown die \"{}\".", name))),
+    };
+    if number != number.floor() {
+        return Err(RollError::Evaluation(String::from("Attempted to roll non-integer number of dice.")));
+    } else if number.is_sign_negative() {
+        return Err(RollError::Evaluation(String::from("Attempted to roll negative number of dice.")));
+    }
+    let number_as_int = match number.normalize().to_u64() {
+        Some(number_as_int) => number_as_int,
+        None => return Err(RollError::Evaluation(format!("Attempted to roll {} dice, which is more than can be counted.", number))),
+    };
+    tally.add(number_as_int)?;
+
+    let mut symbols = SymbolPool::new();
+    let mut values = Vec::new();
+    let mut faces = Vec::new();
+    for _ in 0..number_as_int {
+        match die.roll(rng) {
+            DieFace::Number(value) => {
+                values.push(*value);
+                faces.push(value.to_string());
+            }
+            DieFace::Labelled { value, label } => {
+                values.push(*value);
+                faces.push(format!("{}={}", label, value));
+            }
+            DieFace::Symbols(face) => {
+                for symbol in face {
+                    symbols.add(symbol, 1);
+                }
+                faces.push(if face.is_empty() { String::from("blank") } else { face.join("+") });
             }
-            _ => panic!("Found binop(s) where non-binops were expected."),
-        }
-        if sequence.len() > next_dice_position + 2 {
-            new_sequence.append(&mut sequence[next_dice_position + 2..].to_vec());
         }
+    }
 
-        sequence = new_sequence;
-        next_dice_check = sequence.iter().position(|member| match member {
-            BinopSequenceMember::Binop(Binop::Dice) => true,
-            _ => false,
-        });
+    let processed_string = format!("[{}]", faces.join(", "));
+    if die.is_symbolic() {
+        Ok(RollInformation::new(RollValue::Symbols(symbols), processed_string))
+    } else {
+        RollInformation::new_pool(values, processed_string)
     }
+}
 
-    Ok(sequence)
+pub(crate) fn format_rolls(rolls: &[Decimal]) -> String {
+    format!("[{}]", rolls.iter().map(|dec| dec.to_string()).collect::<Vec<String>>().join(", "))
 }
 
-fn handle_binop_sequence_times_divide_mod(mut sequence: Vec<BinopSequenceMember>) -> Result<Vec<BinopSequenceMember>, String> {
-    let mut next_operator_check = sequence.iter().position(|member| match member {
-        BinopSequenceMember::Binop(Binop::Times) | BinopSequenceMember::Binop(Binop::Divide) | BinopSequenceMember::Binop(Binop::Mod) => true,
-        _ => false,
-    });
+fn summary_sum(frequencies: &BTreeMap<Decimal, u64>) -> Option<Decimal> {
+    let totals: Option<Vec<Decimal>> = frequencies.iter().map(|(face, times)| face.checked_mul(Decimal::from(*times))).collect();
+    totals.and_then(|totals| checked_sum(totals.into_iter()))
+}
 
-    while next_operator_check != None {
-        let next_operator_position = next_operator_check.unwrap();
-        let next_operator = match sequence[next_operator_position].clone() {
-            BinopSequenceMember::Binop(b) => b,
-            _ => panic!("Found non-binop where binop was expected."),
-        };
-        let mut new_sequence = Vec::new();
-
-        new_sequence.append(&mut sequence[..next_operator_position - 1].to_vec());
-        match (sequence[next_operator_position - 1].clone(), sequence[next_operator_position + 1].clone()) {
-            (BinopSequenceMember::NonBinop(info1), BinopSequenceMember::NonBinop(info2)) => {
-                let (value, processed_string) = match next_operator {
-                    Binop::Times => {
-                        let value = info1.value * info2.value;
-                        let processed_string = format!("{} * {}", info1.processed_string, info2.processed_string);
-                        (value, processed_string)
-                    }
-                    Binop::Divide => {
-                        let value = info1.value / info2.value;
-                        let processed_string = format!("{} / {}", info1.processed_string, info2.processed_string);
-                        (value, processed_string)
-                    }
-                    Binop::Mod => {
-                        let value = info1.value % info2.value;
-                        let processed_string = format!("{} % {}", info1.processed_string, info2.processed_string);
-                        (value, processed_string)
-                    }
-                    _ => panic!("Found binop of incorrect type."),
-                };
+/// Renders a face frequency table like `[100000 dice: sum 349921, 1×16702, 2×16581, ...]`.
+fn format_summary(frequencies: &BTreeMap<Decimal, u64>) -> Result<String, RollError> {
+    let count: u64 = frequencies.values().sum();
+    let faces = frequencies.iter().map(|(face, times)| format!("{}×{}", face, times)).collect::<Vec<String>>().join(", ");
+    let sum = summary_sum(frequencies).ok_or_else(|| overflow_error(&format!("[{} dice: {}]", count, faces)))?;
+
+    Ok(format!("[{} dice: sum {}, {}]", count, sum, faces))
+}
+
+/// Applies a pool modifier to a summarized group by way of its face frequencies, so that it means the same as it would on the listed dice.
+fn apply_summary_modifier(processed_string: &str, frequencies: BTreeMap<Decimal, u64>, modifier: &PoolModifier) -> Result<RollInformation, RollError> {
+    let modified: BTreeMap<Decimal, u64> = match modifier {
+        PoolModifier::Sort => frequencies,
+        PoolModifier::Unique => frequencies.keys().map(|face| (*face, 1)).collect(),
+        PoolModifier::Count(face) => {
+            let count = Decimal::from(frequencies.get(face).copied().unwrap_or(0));
+            return Ok(RollInformation::new(count, format!("{}c{} -> {}", processed_string, face, count)));
+        }
+        PoolModifier::Matching => frequencies.into_iter().max_by_key(|(face, times)| (*times, *face)).into_iter().collect(),
+    };
+    let processed_string = format!("{}{} -> {}", processed_string, modifier, format_summary(&modified)?);
+
+    RollInformation::new_summary(modified, processed_string)
+}
 
-                new_sequence.push(BinopSequenceMember::NonBinop(RollInformation::new(value, processed_string)));
+fn apply_pool_modifier(info: RollInformation, modifier: &PoolModifier) -> Result<RollInformation, RollError> {
+    let pool = match (info.pool, info.frequencies) {
+        (Some(pool), _) => pool,
+        (None, Some(frequencies)) => return Ok(apply_summary_modifier(&info.processed_string, frequencies, modifier)?.with_attack_die(info.attack_die)),
+        (None, None) => return Err(RollError::Evaluation(String::from("Attempted to apply a pool operation to a value which isn't a dice pool."))),
+    };
+
+    let information = match modifier {
+        PoolModifier::Sort => {
+            let mut sorted = pool;
+            sorted.sort();
+            RollInformation::new_pool(sorted.clone(), format!("{}s -> {}", info.processed_string, format_rolls(&sorted)))?
+        }
+        PoolModifier::Unique => {
+            let mut unique: Vec<Decimal> = Vec::new();
+            for face in pool.iter() {
+                if !unique.contains(face) {
+                    unique.push(*face);
+                }
             }
-            _ => panic!("Found binop(s) where non-binops were expected."),
+            RollInformation::new_pool(unique.clone(), format!("{}u -> {}", info.processed_string, format_rolls(&unique)))?
         }
-        if sequence.len() > next_operator_position + 2 {
-            new_sequence.append(&mut sequence[next_operator_position + 2..].to_vec());
+        PoolModifier::Count(face) => {
+            let count = Decimal::from(pool.iter().filter(|die| *die == face).count());
+            RollInformation::new(count, format!("{}c{} -> {}", info.processed_string, face, count))
         }
+        PoolModifier::Matching => {
+            let mut best_set: Vec<Decimal> = Vec::new();
+            for face in pool.iter() {
+                let set_size = pool.iter().filter(|die| *die == face).count();
+                if set_size > best_set.len() || (set_size == best_set.len() && Some(face) > best_set.first()) {
+                    best_set = vec![*face; set_size];
+                }
+            }
+            RollInformation::new_pool(best_set.clone(), format!("{}m -> {}", info.processed_string, format_rolls(&best_set)))?
+        }
+    };
 
-        sequence = new_sequence;
-        next_operator_check = sequence.iter().position(|member| match member {
-            BinopSequenceMember::Binop(Binop::Times) | BinopSequenceMember::Binop(Binop::Divide) | BinopSequenceMember::Binop(Binop::Mod) => true,
-            _ => false,
-        });
-    }
-
-    Ok(sequence)
+    Ok(information.with_attack_die(info.attack_die))
 }
 
-fn handle_binop_sequence_plus_minus(mut sequence: Vec<BinopSequenceMember>) -> Result<Vec<BinopSequenceMember>, String> {
-    let mut next_operator_check = sequence.iter().position(|member| match member {
-        BinopSequenceMember::Binop(Binop::Plus) | BinopSequenceMember::Binop(Binop::Minus) => true,
-        _ => false,
-    });
-
-    while next_operator_check != None {
-        let next_operator_position = next_operator_check.unwrap();
-        let next_operator = match sequence[next_operator_position].clone() {
-            BinopSequenceMember::Binop(b) => b,
-            _ => panic!("Found non-binop where binop was expected."),
+fn roll_dice_group(number: &RollInformation, sides: &RollInformation, context: &EvaluationContext, tally: &DiceTally, rng: &mut dyn RngCore) -> Result<RollInformation, RollError> {
+    let (number_string, number, sides) = (&number.processed_string, number.value.number()?.normalize(), sides.value.number()?.normalize());
+    let summarize = match context.summarize_above {
+        Some(threshold) => number > Decimal::from(threshold),
+        None => false,
+    };
+
+    if summarize {
+        roll_dice_summarized(number, sides, context, tally, rng)
+    } else if context.critical {
+        match context.crit_mode {
+            CritMode::Double => {
+                let doubled = number.checked_mul(Decimal::from(2)).ok_or_else(|| overflow_error(&format!("{} * 2", number_string)))?;
+                let (_, new_rolls) = roll_dice(doubled, sides, tally, rng)?;
+                let processed_string = format_rolls(&new_rolls);
+                RollInformation::new_pool(new_rolls, processed_string)
+            }
+            CritMode::Maximize => {
+                let (_, mut new_rolls) = roll_dice(number, sides, tally, rng)?;
+                let maximized_rolls = vec![sides; new_rolls.len()];
+                let processed_string = format!("{} + {}", format_rolls(&new_rolls), format_rolls(&maximized_rolls));
+                new_rolls.extend(maximized_rolls);
+                RollInformation::new_pool(new_rolls, processed_string)
+            }
+        }
+    } else {
+        let (value, new_rolls) = roll_dice(number, sides, tally, rng)?;
+        let attack_die = match context.attack_die_sides {
+            Some(attack_sides) if number == Decimal::from(1) && sides == Decimal::from(attack_sides) => Some(AttackDie { face: value, sides }),
+            _ => None,
         };
-        let mut new_sequence = Vec::new();
-
-        new_sequence.append(&mut sequence[..next_operator_position - 1].to_vec());
-        match (sequence[next_operator_position - 1].clone(), sequence[next_operator_position + 1].clone()) {
-            (BinopSequenceMember::NonBinop(info1), BinopSequenceMember::NonBinop(info2)) => {
-                let (value, processed_string) = match next_operator {
-                    Binop::Plus => {
-                        let value = info1.value + info2.value;
-                        let processed_string = format!("{} + {}", info1.processed_string, info2.processed_string);
-                        (value, processed_string)
-                    }
-                    Binop::Minus => {
-                        let value = info1.value - info2.value;
-                        let processed_string = format!("{} - {}", info1.processed_string, info2.processed_string);
-                        (value, processed_string)
-                    }
-                    _ => panic!("Found binop of incorrect type."),
-                };
+        let processed_string = format_rolls(&new_rolls);
+        Ok(RollInformation::new_pool(new_rolls, processed_string)?.with_attack_die(attack_die))
+    }
+}
 
-                new_sequence.push(BinopSequenceMember::NonBinop(RollInformation::new(value, processed_string)));
+fn apply_binary_operator(operator: BinaryOperator, info1: RollInformation, info2: RollInformation, processed_string: String) -> Result<RollInformation, RollError> {
+    let value = match operator {
+        BinaryOperator::Plus => match (&info1.value, &info2.value) {
+            (RollValue::Symbols(symbols1), RollValue::Symbols(symbols2)) => RollValue::Symbols(symbols1.merged(symbols2)),
+            _ => apply_arithmetic(&info1.value, &info2.value, |a, b| a.checked_add(b), |a, b| a.checked_add(b), &processed_string)?,
+        },
+        BinaryOperator::Minus => apply_arithmetic(&info1.value, &info2.value, |a, b| a.checked_sub(b), |a, b| a.checked_sub(b), &processed_string)?,
+        BinaryOperator::Times => apply_arithmetic(&info1.value, &info2.value, |a, b| a.checked_mul(b), |a, b| a.checked_mul(b), &processed_string)?,
+        BinaryOperator::Divide => {
+            if info2.value.rational()?.is_zero() {
+                return Err(RollError::Evaluation(format!("Attempted to divide by zero in {}.", processed_string)));
             }
-            _ => panic!("Found binop(s) where non-binops were expected."),
+            apply_arithmetic(&info1.value, &info2.value, |a, b| a.checked_div(b), |a, b| a.checked_div(b), &processed_string)?
         }
-        if sequence.len() > next_operator_position + 2 {
-            new_sequence.append(&mut sequence[next_operator_position + 2..].to_vec());
+        BinaryOperator::Mod => {
+            if info2.value.rational()?.is_zero() {
+                return Err(RollError::Evaluation(format!("Attempted to take a remainder modulo zero in {}.", processed_string)));
+            }
+            apply_arithmetic(&info1.value, &info2.value, |a, b| a.checked_rem(b), |a, b| a.checked_rem(b), &processed_string)?
         }
+    };
 
-        sequence = new_sequence;
-        next_operator_check = sequence.iter().position(|member| match member {
-            BinopSequenceMember::Binop(Binop::Plus) | BinopSequenceMember::Binop(Binop::Minus) => true,
-            _ => false,
-        });
-    }
+    Ok(RollInformation::new(value, processed_string).with_attack_die(info1.attack_die.or(info2.attack_die)))
+}
 
-    Ok(sequence)
+fn literal_value(number: Decimal, context: &EvaluationContext) -> Result<RollValue, RollError> {
+    match context.arithmetic {
+        ArithmeticMode::Decimal => Ok(RollValue::Number(number)),
+        ArithmeticMode::Rational => RollValue::Number(number).rational().map(RollValue::Rational),
+    }
 }
 
-fn handle_binop_sequence(sequence: Vec<BinopSequenceMember>) -> Result<RollInformation, String> {
-    let dice_handled = handle_binop_sequence_dice(sequence)?;
-    let times_divide_mod_handled = handle_binop_sequence_times_divide_mod(dice_handled)?;
-    let plus_minus_handled = handle_binop_sequence_plus_minus(times_divide_mod_handled)?;
+fn evaluate(expression: &DiceExpr, context: &EvaluationContext, tally: &DiceTally, rng: &mut dyn RngCore) -> Result<RollInformation, RollError> {
+    match expression {
+        DiceExpr::Number(number) => Ok(RollInformation::new(literal_value(*number, context)?, number.to_string())),
+        DiceExpr::Negate(operand) => {
+            let information = evaluate(operand, context, tally, rng)?;
+            let processed_string = format!("-{}", parenthesize(information.processed_string, operand, ATOM));
+            let value = match information.value {
+                RollValue::Rational(rational) => RollValue::Rational(rational.checked_neg().ok_or_else(|| overflow_error(&processed_string))?),
+                _ => RollValue::Number(-information.value.number()?),
+            };
+            Ok(RollInformation::new(value, processed_string).with_attack_die(information.attack_die))
+        }
+        DiceExpr::Binary(operator, left, right) => {
+            let info1 = evaluate(left, context, tally, rng)?;
+            let info2 = evaluate(right, context, tally, rng)?;
+            let processed_string = format!(
+                "{} {} {}",
+                parenthesize(info1.processed_string.clone(), left, operator.precedence()),
+                operator,
+                parenthesize(info2.processed_string.clone(), right, operator.precedence() + 1)
+            );
+            apply_binary_operator(*operator, info1, info2, processed_string)
+        }
+        DiceExpr::Dice(number, sides) => {
+            let number = evaluate(number, context, tally, rng)?;
+            let sides = evaluate(sides, context, tally, rng)?;
+            roll_dice_group(&number, &sides, context, tally, rng)
+        }
+        DiceExpr::NamedDice(number, name) => {
+            let number = evaluate(number, context, tally, rng)?;
+            roll_named_dice(number.value.number()?, name, context, tally, rng)
+        }
+        DiceExpr::Modified(pool, modifier) => apply_pool_modifier(evaluate(pool, context, tally, rng)?, modifier),
+        DiceExpr::Crit(inner) => {
+            let critical_context = EvaluationContext { critical: true, ..context.clone() };
+            let information = evaluate(inner, &critical_context, tally, rng)?;
+            Ok(RollInformation {
+                processed_string: format!("crit({})", information.processed_string),
+                ..information
+            })
+        }
+        DiceExpr::Percentile(skill, extra_tens_dice) => {
+            let skill = evaluate(skill, context, tally, rng)?;
+            tally.add(extra_tens_dice.unsigned_abs() + 2)?;
+            let percentile = roll_percentile(skill.value.number()?, *extra_tens_dice, rng);
+            Ok(RollInformation {
+                processed_string: format!("coc({}){}: {}", skill.processed_string, format_percentile_modifier(*extra_tens_dice), percentile.processed_string),
+                ..percentile
+            })
+        }
+    }
+}
 
-    if plus_minus_handled.len() != 1 {
-        panic!("Incorrect length at the end of binop-sequence-handling.")
-    } else if let BinopSequenceMember::NonBinop(info) = plus_minus_handled[0].clone() {
-        Ok(info)
-    } else {
-        panic!("Incorrect type at the end of binop-sequence-handling.")
+fn nesting_depth(input: &str) -> u64 {
+    let (mut depth, mut deepest) = (0u64, 0u64);
+    for c in input.chars() {
+        match c {
+            '(' => {
+                depth += 1;
+                deepest = deepest.max(depth);
+            }
+            ')' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
     }
+
+    deepest
 }
 
 pub fn clean_input(input: &str) -> String {
@@ -243,13 +679,20 @@ pub fn clean_input(input: &str) -> String {
 //   Main Parser   //
 /////////////////////
 
-fn parse_number(number: Pair<Rule>) -> (Decimal, String) {
+fn parse_number(number: Pair<Rule>) -> Result<Decimal, RollError> {
     assert_eq!(number.as_rule(), Rule::number, "Called parse_number on non-number.");
 
     let mut number_as_string = String::from(number.as_str());
     number_as_string.retain(|c| !c.is_whitespace());
 
-    (Decimal::from_str(&number_as_string).unwrap(), number_as_string)
+    // Decimal rounds away excess digits rather than failing, so check the integer part fits before parsing.
+    let integer_digits = number_as_string.split('.').next().unwrap_or("").trim_start_matches('0');
+    let max_digits = Decimal::MAX.to_string();
+    let too_large = integer_digits.len() > max_digits.len() || (integer_digits.len() == max_digits.len() && integer_digits > max_digits.as_str());
+    match Decimal::from_str(&number_as_string) {
+        Ok(value) if !too_large => Ok(value),
+        _ => Err(RollError::Evaluation(format!("The number {} is too large to represent.", number_as_string))),
+    }
 }
 
 fn parse_binop(binop: Pair<Rule>) -> Binop {
@@ -280,45 +723,171 @@ fn parse_unop(unop: Pair<Rule>) -> Unop {
     }
 }
 
-fn parse_paren_block(paren_block: Pair<Rule>) -> Result<RollInformation, String> {
-    assert_eq!(paren_block.as_rule(), Rule::paren_block, "Called parse_paren_block on non-paren-block.");
+fn parse_pool_modifier(pool_modifier: Pair<Rule>) -> Result<PoolModifier, RollError> {
+    assert_eq!(pool_modifier.as_rule(), Rule::pool_modifier, "Called parse_pool_modifier on non-pool-modifier.");
 
-    let mut inside = paren_block.into_inner();
-    let mut binop_sequence_vec = Vec::new();
-    let information = parse_legitimate_sequence(inside.next().unwrap())?;
-    binop_sequence_vec.push(BinopSequenceMember::NonBinop(information));
+    let internal_modifier = pool_modifier.into_inner().next().unwrap();
 
-    let mut next = inside.next();
-    while next != None {
-        let next_binop = parse_binop(next.unwrap());
-        binop_sequence_vec.push(BinopSequenceMember::Binop(next_binop));
+    match internal_modifier.as_rule() {
+        Rule::sort_modifier => Ok(PoolModifier::Sort),
+        Rule::unique_modifier => Ok(PoolModifier::Unique),
+        Rule::count_modifier => Ok(PoolModifier::Count(parse_number(internal_modifier.into_inner().next().unwrap())?)),
+        Rule::matching_modifier => Ok(PoolModifier::Matching),
+        _ => unreachable!("Non-pool-modifier found inside pool_modifier token."),
+    }
+}
 
-        let next_information = parse_legitimate_sequence(inside.next().unwrap())?;
-        binop_sequence_vec.push(BinopSequenceMember::NonBinop(next_information));
+fn pop_expression(sequence: &mut Vec<SequenceMember>) -> DiceExpr {
+    match sequence.pop() {
+        Some(SequenceMember::Expr(expression)) => expression,
+        _ => panic!("Found binop where non-binop was expected."),
+    }
+}
 
-        next = inside.next();
+fn next_expression(members: &mut impl Iterator<Item = SequenceMember>) -> DiceExpr {
+    match members.next() {
+        Some(SequenceMember::Expr(expression)) => expression,
+        _ => panic!("Found binop where non-binop was expected."),
+    }
+}
+
+/// Groups dice operators and pool modifiers with their operands, left to right, since they bind tightest.
+fn build_dice(sequence: Vec<SequenceMember>) -> Result<Vec<SequenceMember>, RollError> {
+    let mut built = Vec::new();
+    let mut members = sequence.into_iter();
+
+    while let Some(member) = members.next() {
+        match member {
+            SequenceMember::Binop(Binop::Dice) => {
+                let number = Box::new(pop_expression(&mut built));
+                let dice = match members.next() {
+                    Some(SequenceMember::Expr(sides)) => DiceExpr::Dice(number, Box::new(sides)),
+                    Some(SequenceMember::DieName(name)) => DiceExpr::NamedDice(number, name),
+                    _ => panic!("Found binop where non-binop was expected."),
+                };
+                built.push(SequenceMember::Expr(dice));
+            }
+            SequenceMember::Modifier(modifier) => {
+                let pool = Box::new(pop_expression(&mut built));
+                built.push(SequenceMember::Expr(DiceExpr::Modified(pool, modifier)));
+            }
+            SequenceMember::DieName(name) => return Err(RollError::Syntax(format!("Found die name \"{}\" without a dice operator before it.", name))),
+            other => built.push(other),
+        }
+    }
+
+    Ok(built)
+}
+
+/// Groups every binop which `operator_of` recognizes with its operands, left to right.
+fn build_binary_operators(sequence: Vec<SequenceMember>, operator_of: fn(&Binop) -> Option<BinaryOperator>) -> Vec<SequenceMember> {
+    let mut built = Vec::new();
+    let mut members = sequence.into_iter();
+
+    while let Some(member) = members.next() {
+        match member {
+            SequenceMember::Binop(binop) => match operator_of(&binop) {
+                Some(operator) => {
+                    let left = pop_expression(&mut built);
+                    let right = next_expression(&mut members);
+                    built.push(SequenceMember::Expr(DiceExpr::Binary(operator, Box::new(left), Box::new(right))));
+                }
+                None => built.push(SequenceMember::Binop(binop)),
+            },
+            other => built.push(other),
+        }
     }
 
-    let handled = handle_binop_sequence(binop_sequence_vec)?;
-    Ok(RollInformation::new(handled.value, format!("({})", handled.processed_string)))
+    built
 }
 
-fn parse_non_operator(non_operator: Pair<Rule>) -> Result<RollInformation, String> {
+fn build_sequence(sequence: Vec<SequenceMember>) -> Result<DiceExpr, RollError> {
+    let dice_built = build_dice(sequence)?;
+    let times_divide_mod_built = build_binary_operators(dice_built, |binop| match binop {
+        Binop::Times => Some(BinaryOperator::Times),
+        Binop::Divide => Some(BinaryOperator::Divide),
+        Binop::Mod => Some(BinaryOperator::Mod),
+        _ => None,
+    });
+    let mut plus_minus_built = build_binary_operators(times_divide_mod_built, |binop| match binop {
+        Binop::Plus => Some(BinaryOperator::Plus),
+        Binop::Minus => Some(BinaryOperator::Minus),
+        _ => None,
+    });
+
+    if plus_minus_built.len() != 1 {
+        panic!("Incorrect length at the end of binop-sequence-handling.")
+    }
+    Ok(pop_expression(&mut plus_minus_built))
+}
+
+fn parse_paren_block(paren_block: Pair<Rule>) -> Result<DiceExpr, RollError> {
+    assert_eq!(paren_block.as_rule(), Rule::paren_block, "Called parse_paren_block on non-paren-block.");
+
+    let mut sequence = Vec::new();
+    for member in paren_block.into_inner() {
+        match member.as_rule() {
+            Rule::legitimate_sequence => sequence.push(SequenceMember::Expr(parse_legitimate_sequence(member)?)),
+            Rule::binop => sequence.push(SequenceMember::Binop(parse_binop(member))),
+            _ => unreachable!("Paren block contains token other than legitimate_sequence or binop."),
+        }
+    }
+
+    build_sequence(sequence)
+}
+
+fn parse_crit_block(crit_block: Pair<Rule>) -> Result<DiceExpr, RollError> {
+    assert_eq!(crit_block.as_rule(), Rule::crit_block, "Called parse_crit_block on non-crit-block.");
+
+    let paren_block = parse_paren_block(crit_block.into_inner().next().unwrap())?;
+    Ok(DiceExpr::Crit(Box::new(paren_block)))
+}
+
+fn parse_percentile_block(percentile_block: Pair<Rule>) -> Result<DiceExpr, RollError> {
+    assert_eq!(percentile_block.as_rule(), Rule::percentile_block, "Called parse_percentile_block on non-percentile-block.");
+
+    let mut inside = percentile_block.into_inner();
+    let skill = parse_paren_block(inside.next().unwrap())?;
+
+    let extra_tens_dice = match inside.next() {
+        None => 0,
+        Some(percentile_modifier) => {
+            let modifier = percentile_modifier.into_inner().next().unwrap();
+            let rule = modifier.as_rule();
+            let count = match modifier.into_inner().next() {
+                None => Decimal::from(1),
+                Some(number) => parse_number(number)?,
+            };
+            let count = match count.to_i64() {
+                Some(integer) if Decimal::from(integer) == count => integer,
+                _ => return Err(RollError::Evaluation(String::from("Attempted to roll a non-integer number of bonus or penalty dice."))),
+            };
+            match rule {
+                Rule::bonus_dice => count,
+                Rule::penalty_dice => -count,
+                _ => unreachable!("Non-bonus-or-penalty token found inside percentile_modifier token."),
+            }
+        }
+    };
+
+    Ok(DiceExpr::Percentile(Box::new(skill), extra_tens_dice))
+}
+
+fn parse_non_operator(non_operator: Pair<Rule>) -> Result<DiceExpr, RollError> {
     assert_eq!(non_operator.as_rule(), Rule::non_operator, "Called parse_non_operator on non-paren-block.");
 
     let inside = non_operator.into_inner().next().unwrap();
 
     match inside.as_rule() {
-        Rule::number => {
-            let (number, string) = parse_number(inside);
-            Ok(RollInformation::new(number, string))
-        }
+        Rule::number => Ok(DiceExpr::Number(parse_number(inside)?)),
         Rule::paren_block => parse_paren_block(inside),
-        _ => unreachable!("Non-operator token inside isn't a number or paren block."),
+        Rule::crit_block => parse_crit_block(inside),
+        Rule::percentile_block => parse_percentile_block(inside),
+        _ => unreachable!("Non-operator token inside isn't a number, paren block, crit block, or percentile block."),
     }
 }
 
-fn parse_paired_unop(paired_unop: Pair<Rule>) -> Result<RollInformation, String> {
+fn parse_paired_unop(paired_unop: Pair<Rule>) -> Result<DiceExpr, RollError> {
     assert_eq!(paired_unop.as_rule(), Rule::paired_unop, "Called parse_paired_unop on non-paired-unop.");
 
     let mut inside = paired_unop.into_inner();
@@ -327,64 +896,79 @@ fn parse_paired_unop(paired_unop: Pair<Rule>) -> Result<RollInformation, String>
     let non_op = parse_non_operator(inside.next().unwrap())?;
 
     match parse_unop(unop) {
-        Unop::Plus => Ok(RollInformation::new(non_op.value, format!("+{}", non_op.processed_string))),
-        Unop::Minus => Ok(RollInformation::new(non_op.value * Decimal::from(-1), format!("-{}", non_op.processed_string))),
+        Unop::Plus => Ok(non_op),
+        Unop::Minus => match non_op {
+            DiceExpr::Number(number) => Ok(DiceExpr::Number(-number)),
+            _ => Ok(DiceExpr::Negate(Box::new(non_op))),
+        },
     }
 }
 
-fn parse_non_binop(non_binop: Pair<Rule>) -> Result<RollInformation, String> {
+fn parse_non_binop(non_binop: Pair<Rule>) -> Result<SequenceMember, RollError> {
     assert_eq!(non_binop.as_rule(), Rule::non_binop, "Called parse_non_binop on non-non-binop.");
 
     let inside = non_binop.into_inner().next().unwrap();
 
-    match inside.as_rule() {
-        Rule::number => {
-            let (number, string) = parse_number(inside);
-            Ok(RollInformation::new(number, string))
-        }
-        Rule::paren_block => parse_paren_block(inside),
-        Rule::paired_unop => parse_paired_unop(inside),
-        _ => unreachable!("Non-binop token inside isn't a number, paren block, or paired unop."),
-    }
+    let expression = match inside.as_rule() {
+        Rule::number => DiceExpr::Number(parse_number(inside)?),
+        Rule::paren_block => parse_paren_block(inside)?,
+        Rule::crit_block => parse_crit_block(inside)?,
+        Rule::percentile_block => parse_percentile_block(inside)?,
+        Rule::paired_unop => parse_paired_unop(inside)?,
+        Rule::die_name => return Ok(SequenceMember::DieName(String::from(inside.as_str()))),
+        _ => unreachable!("Non-binop token inside isn't a number, paren block, crit block, percentile block, paired unop, or die name."),
+    };
+
+    Ok(SequenceMember::Expr(expression))
 }
 
-fn parse_legitimate_sequence(sequence: Pair<Rule>) -> Result<RollInformation, String> {
+fn parse_legitimate_sequence(sequence: Pair<Rule>) -> Result<DiceExpr, RollError> {
     assert_eq!(sequence.as_rule(), Rule::legitimate_sequence, "Called parse_legitimate_sequence on non-legitimate-sequence.");
 
-    let mut inside = sequence.into_inner();
-    let mut binop_sequence_vec = Vec::new();
-    let information = parse_non_binop(inside.next().unwrap())?;
-    binop_sequence_vec.push(BinopSequenceMember::NonBinop(information));
-
-    let mut next = inside.next();
-    while next != None {
-        let next_binop = parse_binop(next.unwrap());
-        binop_sequence_vec.push(BinopSequenceMember::Binop(next_binop));
-
-        let next_information = parse_non_binop(inside.next().unwrap())?;
-        binop_sequence_vec.push(BinopSequenceMember::NonBinop(next_information));
-
-        next = inside.next();
+    let mut members = Vec::new();
+    for member in sequence.into_inner() {
+        match member.as_rule() {
+            Rule::non_binop => members.push(parse_non_binop(member)?),
+            Rule::binop => members.push(SequenceMember::Binop(parse_binop(member))),
+            Rule::pool_modifier => members.push(SequenceMember::Modifier(parse_pool_modifier(member)?)),
+            _ => unreachable!("Legitimate sequence contains token other than non_binop, binop, or pool_modifier."),
+        }
     }
 
-    Ok(handle_binop_sequence(binop_sequence_vec)?)
+    build_sequence(members)
 }
 
-fn parse_full_expression(mut tree: Pairs<Rule>) -> Result<RollInformation, String> {
+fn parse_full_expression(mut tree: Pairs<Rule>) -> Result<DiceExpr, RollError> {
     let full_expression = tree.next().unwrap();
     let sequence = full_expression.into_inner().next().unwrap();
 
     parse_legitimate_sequence(sequence)
 }
 
-pub fn parse_input(input: &str) -> Result<RollInformation, String> {
+/// Parses `input` into a [`CompiledRoll`], checking its length and nesting against the default resource limits.
+pub fn parse(input: &str) -> Result<CompiledRoll, RollError> {
+    parse_with_limits(input, &ResourceLimits::new())
+}
+
+pub fn parse_with_limits(input: &str, limits: &ResourceLimits) -> Result<CompiledRoll, RollError> {
     let cleaned = clean_input(input);
+    limits.check(Limit::InputLength, cleaned.chars().count() as u64)?;
+    limits.check(Limit::NestingDepth, nesting_depth(&cleaned))?; // Checked before parsing, since the parser recurses once per level.
+
     match DiceParser::parse(Rule::full_expression, &cleaned) {
-        Ok(full_expression) => Ok(parse_full_expression(full_expression)?),
-        Err(e) => Err(e.to_string()),
+        Ok(full_expression) => Ok(CompiledRoll::new(parse_full_expression(full_expression)?)),
+        Err(e) => Err(RollError::Syntax(e.to_string())),
     }
 }
 
+pub fn parse_input(input: &str) -> Result<RollInformation, RollError> {
+    parse_input_with_context(input, &EvaluationContext::new())
+}
+
+pub fn parse_input_with_context(input: &str, context: &EvaluationContext) -> Result<RollInformation, RollError> {
+    parse_with_limits(input, &context.limits)?.roll(context)
+}
+
 /////////////////////
 //   GUI Helpers   //
 /////////////////////
@@ -411,8 +995,8 @@ pub fn get_last_input(input: &str) -> (String, Option<Rule>) {
                     let operator = latest_input.into_inner().next().unwrap();
                     (String::from(operator.as_str()), Some(operator.as_rule()))
                 }
-                Rule::paren => (String::from(latest_input.as_str()), Some(latest_input.as_rule())),
-                _ => unreachable!("Flat sequence contains token other than number_liberal, binop, unop, or paren.")
+                Rule::paren | Rule::crit_keyword | Rule::percentile_keyword | Rule::word => (String::from(latest_input.as_str()), Some(latest_input.as_rule())),
+                _ => unreachable!("Flat sequence contains token other than number_liberal, binop, unop, paren, crit_keyword, percentile_keyword, or word.")
             }
         }
     }
@@ -0,0 +1,148 @@
+use crate::parse::RollValue;
+use rust_decimal::prelude::*;
+use rust_decimal::RoundingStrategy;
+use serde::{Deserialize, Serialize};
+
+/// How a result is rounded when it has more decimal places than are shown.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub enum RoundingMode {
+    /// Round to nearest, with halves rounded away from zero.
+    #[default]
+    HalfUp,
+    /// Round to nearest, with halves rounded to the even neighbour.
+    Bankers,
+    /// Round towards negative infinity.
+    Floor,
+    /// Drop the extra digits.
+    TowardZero,
+}
+
+impl RoundingMode {
+    fn strategy(&self) -> RoundingStrategy {
+        match self {
+            RoundingMode::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            RoundingMode::Bankers => RoundingStrategy::MidpointNearestEven,
+            RoundingMode::Floor => RoundingStrategy::ToNegativeInfinity,
+            RoundingMode::TowardZero => RoundingStrategy::ToZero,
+        }
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RoundingMode, String> {
+        match s.to_lowercase().as_str() {
+            "half-up" | "halfup" => Ok(RoundingMode::HalfUp),
+            "bankers" | "half-even" => Ok(RoundingMode::Bankers),
+            "floor" => Ok(RoundingMode::Floor),
+            "toward-zero" | "truncate" => Ok(RoundingMode::TowardZero),
+            _ => Err(format!("Unknown rounding mode \"{}\"; expected half-up, bankers, floor, or toward-zero.", s)),
+        }
+    }
+}
+
+/// How many digits of a result are shown.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum Precision {
+    /// Round to this many decimal places.
+    Places(u32),
+    /// Show non-integers as fractions, like `10/3`.
+    Rational,
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Precision, String> {
+        if s.to_lowercase() == "rational" {
+            return Ok(Precision::Rational);
+        }
+        match s.parse::<u32>() {
+            Ok(places) if places <= 28 => Ok(Precision::Places(places)),
+            _ => Err(format!("Unknown precision \"{}\"; expected a number of decimal places from 0 to 28, or \"rational\".", s)),
+        }
+    }
+}
+
+/// How results are shown. Only the displayed value is rounded; the roll itself and its trace keep full precision.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default)]
+pub struct DisplayPolicy {
+    pub rounding: RoundingMode,
+    pub precision: Precision,
+}
+
+impl DisplayPolicy {
+    /// Rounds half-up to 4 decimal places, so `10/3` shows as `3.3333` rather than every one of its 28 digits.
+    pub fn new() -> DisplayPolicy {
+        DisplayPolicy {
+            rounding: RoundingMode::HalfUp,
+            precision: Precision::Places(4),
+        }
+    }
+    pub fn format_number(&self, number: Decimal) -> String {
+        match self.precision {
+            Precision::Places(places) => number.round_dp_with_strategy(places, self.rounding.strategy()).normalize().to_string(),
+            Precision::Rational => {
+                let (numerator, denominator) = approximate_fraction(number);
+                if denominator == Decimal::from(1) {
+                    numerator.to_string()
+                } else {
+                    format!("{}/{}", numerator, denominator)
+                }
+            }
+        }
+    }
+    pub fn format_value(&self, value: &RollValue) -> String {
+        match value {
+            RollValue::Number(number) => self.format_number(*number),
+            RollValue::Rational(rational) => match (self.precision, rational.to_decimal()) {
+                (Precision::Places(_), Some(number)) => self.format_number(number),
+                _ => rational.to_string(),
+            },
+            RollValue::Symbols(symbols) => symbols.to_string(),
+        }
+    }
+}
+
+impl Default for DisplayPolicy {
+    fn default() -> DisplayPolicy {
+        DisplayPolicy::new()
+    }
+}
+
+/// Finds the simplest fraction equal to `number` to within 20 decimal places, so that `3.3333333333333333333333333333` reads as `10/3`.
+fn approximate_fraction(number: Decimal) -> (Decimal, Decimal) {
+    let target = number.abs();
+    let tolerance = Decimal::new(1, 20);
+    let (mut previous_numerator, mut previous_denominator) = (Decimal::from(0), Decimal::from(1));
+    let (mut numerator, mut denominator) = (Decimal::from(1), Decimal::from(0));
+    let mut remainder = target;
+
+    loop {
+        let whole = remainder.floor();
+        let next_numerator = whole.checked_mul(numerator).and_then(|product| product.checked_add(previous_numerator));
+        let next_denominator = whole.checked_mul(denominator).and_then(|product| product.checked_add(previous_denominator));
+        match (next_numerator, next_denominator) {
+            (Some(next_numerator), Some(next_denominator)) => {
+                previous_numerator = numerator;
+                previous_denominator = denominator;
+                numerator = next_numerator;
+                denominator = next_denominator;
+            }
+            _ => break,
+        }
+
+        let fractional = remainder - whole;
+        if fractional.is_zero() || (numerator / denominator - target).abs() < tolerance {
+            break;
+        }
+        remainder = Decimal::from(1) / fractional;
+    }
+
+    if number.is_sign_negative() {
+        numerator.set_sign_negative(true);
+    }
+    (numerator, denominator)
+}
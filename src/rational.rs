@@ -0,0 +1,139 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// An exact fraction in lowest terms, with a positive denominator.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rational {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl Rational {
+    /// Returns `None` if the denominator is zero, or if the reduced fraction doesn't fit.
+    pub fn new(numerator: i128, denominator: i128) -> Option<Rational> {
+        if denominator == 0 {
+            return None;
+        }
+        let divisor = gcd(numerator, denominator);
+        let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+
+        if denominator < 0 {
+            Some(Rational {
+                numerator: numerator.checked_neg()?,
+                denominator: denominator.checked_neg()?,
+            })
+        } else {
+            Some(Rational { numerator, denominator })
+        }
+    }
+    pub fn from_integer(integer: i128) -> Rational {
+        Rational { numerator: integer, denominator: 1 }
+    }
+    /// Every decimal is exactly a fraction over a power of ten, so this only fails if that power overflows.
+    pub fn from_decimal(decimal: Decimal) -> Option<Rational> {
+        let denominator = 10i128.checked_pow(decimal.scale())?;
+        Rational::new(decimal.mantissa(), denominator)
+    }
+    /// The nearest decimal, or `None` if the value is outside the range of `Decimal`.
+    pub fn to_decimal(&self) -> Option<Decimal> {
+        Decimal::from_i128(self.numerator)?.checked_div(Decimal::from_i128(self.denominator)?)
+    }
+    pub fn numerator(&self) -> i128 {
+        self.numerator
+    }
+    pub fn denominator(&self) -> i128 {
+        self.denominator
+    }
+    pub fn is_zero(&self) -> bool {
+        self.numerator == 0
+    }
+    pub fn checked_add(&self, other: Rational) -> Option<Rational> {
+        let numerator = self.numerator.checked_mul(other.denominator)?.checked_add(other.numerator.checked_mul(self.denominator)?)?;
+        Rational::new(numerator, self.denominator.checked_mul(other.denominator)?)
+    }
+    pub fn checked_sub(&self, other: Rational) -> Option<Rational> {
+        self.checked_add(other.checked_neg()?)
+    }
+    pub fn checked_mul(&self, other: Rational) -> Option<Rational> {
+        // Cross-cancelling first keeps the intermediate products small.
+        let left = gcd(self.numerator, other.denominator);
+        let right = gcd(other.numerator, self.denominator);
+        let numerator = (self.numerator / left).checked_mul(other.numerator / right)?;
+        let denominator = (self.denominator / right).checked_mul(other.denominator / left)?;
+        Rational::new(numerator, denominator)
+    }
+    /// Returns `None` when dividing by zero as well as on overflow.
+    pub fn checked_div(&self, other: Rational) -> Option<Rational> {
+        self.checked_mul(Rational::new(other.denominator, other.numerator)?)
+    }
+    /// The remainder after truncated division, with the sign of `self`, matching `%` on `Decimal`.
+    pub fn checked_rem(&self, other: Rational) -> Option<Rational> {
+        let quotient = self.checked_div(other)?;
+        let whole = Rational::from_integer(quotient.numerator / quotient.denominator);
+        self.checked_sub(other.checked_mul(whole)?)
+    }
+    pub fn checked_neg(&self) -> Option<Rational> {
+        Some(Rational {
+            numerator: self.numerator.checked_neg()?,
+            denominator: self.denominator,
+        })
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+impl FromStr for Rational {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rational, String> {
+        let error = || format!("Invalid fraction \"{}\".", s);
+        let mut parts = s.splitn(2, '/');
+        let numerator = parts.next().unwrap_or("").trim().parse::<i128>().map_err(|_| error())?;
+        let denominator = match parts.next() {
+            Some(denominator) => denominator.trim().parse::<i128>().map_err(|_| error())?,
+            None => 1,
+        };
+
+        Rational::new(numerator, denominator).ok_or_else(error)
+    }
+}
+
+impl TryFrom<String> for Rational {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Rational, String> {
+        s.parse()
+    }
+}
+
+impl From<Rational> for String {
+    fn from(rational: Rational) -> String {
+        rational.to_string()
+    }
+}
+
+/// The greatest common divisor, falling back to 1 when it's zero or too large to divide by as an `i128`.
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+
+    match i128::try_from(a) {
+        Ok(divisor) if divisor != 0 => divisor,
+        _ => 1,
+    }
+}
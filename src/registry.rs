@@ -0,0 +1,170 @@
+use crate::symbols::Cancellation;
+use rand::Rng;
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// One face of a custom die.
+///
+/// In a definitions file, a face is written as a number (`2`), a labelled number (`{"value": 2, "label": "X"}`), or a list of symbols (`["success", "advantage"]`, or `[]` for a blank).
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum DieFace {
+    Number(Decimal),
+    Labelled { value: Decimal, label: String },
+    Symbols(Vec<String>),
+}
+
+impl DieFace {
+    pub fn is_symbolic(&self) -> bool {
+        matches!(self, DieFace::Symbols(_))
+    }
+}
+
+/// A named die with an arbitrary table of faces, rolled as `NdName`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct CustomDie {
+    pub name: String,
+    pub faces: Vec<DieFace>,
+}
+
+impl CustomDie {
+    pub fn new(name: &str, faces: Vec<DieFace>) -> CustomDie {
+        CustomDie { name: String::from(name), faces }
+    }
+    fn new_symbolic(name: &str, faces: &[&[&str]]) -> CustomDie {
+        CustomDie::new(name, faces.iter().map(|face| DieFace::Symbols(face.iter().map(|symbol| String::from(*symbol)).collect())).collect())
+    }
+    /// Whether this die's faces bear symbols rather than numbers.
+    pub fn is_symbolic(&self) -> bool {
+        self.faces.iter().any(|face| face.is_symbolic())
+    }
+    pub fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> &DieFace {
+        &self.faces[rng.gen_range(0..self.faces.len())]
+    }
+    fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphabetic()) {
+            Err(format!("Die name \"{}\" must consist only of letters.", self.name))
+        } else if self.faces.is_empty() {
+            Err(format!("Die \"{}\" has no faces.", self.name))
+        } else if self.is_symbolic() && !self.faces.iter().all(|face| face.is_symbolic()) {
+            Err(format!("Die \"{}\" mixes numeric and symbol faces.", self.name))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The contents of a dice definitions file.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct DiceDefinitions {
+    #[serde(default)]
+    pub dice: Vec<CustomDie>,
+    /// Extra symbol cancellation rules, applied alongside the built-in ones.
+    #[serde(default)]
+    pub cancellations: Vec<Cancellation>,
+}
+
+impl DiceDefinitions {
+    pub fn load(path: &Path) -> Result<DiceDefinitions, String> {
+        let definitions_as_json = read_to_string(path).map_err(|e| format!("Failed to read dice definitions from {}: {}", path.display(), e))?;
+        let definitions: DiceDefinitions = serde_json::from_str(&definitions_as_json).map_err(|e| format!("Failed to parse dice definitions in {}: {}", path.display(), e))?;
+        for die in definitions.dice.iter() {
+            die.validate()?;
+        }
+
+        Ok(definitions)
+    }
+}
+
+/// Every die which can be rolled by name, along with the rules for cancelling their symbols.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiceRegistry {
+    dice: Vec<CustomDie>,
+    pub cancellations: Vec<Cancellation>,
+}
+
+impl DiceRegistry {
+    /// A registry holding only the built-in dice: the narrative dice of Genesys and Fantasy Flight's Star Wars games.
+    /// Triumph and despair also count as a success and failure respectively.
+    pub fn new() -> DiceRegistry {
+        DiceRegistry {
+            dice: vec![
+                CustomDie::new_symbolic("boost", &[&[], &[], &["success"], &["success", "advantage"], &["advantage", "advantage"], &["advantage"]]),
+                CustomDie::new_symbolic("setback", &[&[], &[], &["failure"], &["failure"], &["threat"], &["threat"]]),
+                CustomDie::new_symbolic(
+                    "ability",
+                    &[&[], &["success"], &["success"], &["success", "success"], &["advantage"], &["advantage"], &["success", "advantage"], &["advantage", "advantage"]],
+                ),
+                CustomDie::new_symbolic(
+                    "difficulty",
+                    &[&[], &["failure"], &["failure", "failure"], &["threat"], &["threat"], &["threat"], &["threat", "threat"], &["failure", "threat"]],
+                ),
+                CustomDie::new_symbolic(
+                    "proficiency",
+                    &[
+                        &[],
+                        &["success"],
+                        &["success"],
+                        &["success", "success"],
+                        &["success", "success"],
+                        &["advantage"],
+                        &["success", "advantage"],
+                        &["success", "advantage"],
+                        &["success", "advantage"],
+                        &["advantage", "advantage"],
+                        &["advantage", "advantage"],
+                        &["triumph", "success"],
+                    ],
+                ),
+                CustomDie::new_symbolic(
+                    "challenge",
+                    &[
+                        &[],
+                        &["failure"],
+                        &["failure"],
+                        &["failure", "failure"],
+                        &["failure", "failure"],
+                        &["threat"],
+                        &["threat"],
+                        &["failure", "threat"],
+                        &["failure", "threat"],
+                        &["threat", "threat"],
+                        &["threat", "threat"],
+                        &["despair", "failure"],
+                    ],
+                ),
+            ],
+            cancellations: vec![Cancellation::new("success", "failure"), Cancellation::new("advantage", "threat")],
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CustomDie> {
+        self.dice.iter().find(|die| die.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Adds a die, replacing any existing die of the same name.
+    pub fn register(&mut self, die: CustomDie) -> Result<(), String> {
+        die.validate()?;
+        self.dice.retain(|existing| !existing.name.eq_ignore_ascii_case(&die.name));
+        self.dice.push(die);
+
+        Ok(())
+    }
+
+    pub fn register_definitions(&mut self, definitions: DiceDefinitions) -> Result<(), String> {
+        for die in definitions.dice {
+            self.register(die)?;
+        }
+        self.cancellations.extend(definitions.cancellations);
+
+        Ok(())
+    }
+}
+
+impl Default for DiceRegistry {
+    fn default() -> DiceRegistry {
+        DiceRegistry::new()
+    }
+}
@@ -0,0 +1,244 @@
+//! An append-only log of every roll, kept as JSON Lines in `sessions.jsonl` in the data directory.
+//!
+//! Each line is one [`LogEntry`] object with these fields:
+//!
+//! - `time`: when the roll was made, as an RFC 3339 timestamp in UTC.
+//! - `profile`: the name of the profile it was rolled under, or `null`.
+//! - `input`: the roll as entered, in canonical form when it parsed, or a description such as `Blades in the Dark (2)` for a system roll.
+//! - `result`: `{"Ok": <RollInformation>}`, whose `processed_string` is the trace of every die rolled, or `{"Err": "<message>"}`.
+//! - `rng`: how the dice were randomized. `{"source": "entropy", "seed": n}` is a generator seeded from entropy for this roll alone, so seed `n` reproduces it exactly;
+//!   `{"source": "seeded", "seed": n, "sequence": s}` is a roll in a sequence the user seeded with `s`, which drew `n` to seed this roll's own generator, so it too is
+//!   reproduced by seed `n`; `null` is unknown, as for rolls imported from an older history file.
+//! - `shortcut`: the name of the shortcut which was rolled, or `null`.
+//!
+//! Lines are only ever added, never rewritten, so no crash can lose earlier rolls. The capped history shown by the GUI is a view of the last few entries.
+
+use crate::error::StorageError;
+use crate::parse::RollInformation;
+use crate::storage::{History, HistoryStore};
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fs::{create_dir_all, metadata, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// One roll, as recorded in the session log.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LogEntry {
+    pub time: DateTime<Utc>,
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub input: String,
+    pub result: Result<RollInformation, String>,
+    #[serde(default)]
+    pub rng: Option<RngSource>,
+    #[serde(default)]
+    pub shortcut: Option<String>,
+}
+
+impl LogEntry {
+    /// An entry for a roll made just now, outside any profile and not from a shortcut.
+    pub fn new(input: impl Into<String>, result: Result<RollInformation, String>, rng: Option<RngSource>) -> LogEntry {
+        LogEntry {
+            time: Utc::now(),
+            profile: None,
+            input: input.into(),
+            result,
+            rng,
+            shortcut: None,
+        }
+    }
+}
+
+/// Where the randomness for a roll came from.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum RngSource {
+    /// A generator seeded from entropy for this roll alone, which `seed` reproduces exactly.
+    Entropy { seed: u64 },
+    /// A generator seeded with `seed`, which reproduces the roll exactly, drawn from a sequence the user seeded with `sequence`.
+    Seeded { seed: u64, sequence: u64 },
+}
+
+impl RngSource {
+    /// A generator freshly seeded from entropy, along with the record of its seed.
+    pub fn from_entropy() -> (RngSource, StdRng) {
+        let seed = rand::random();
+        (RngSource::Entropy { seed }, StdRng::seed_from_u64(seed))
+    }
+
+    /// A generator for the next roll in the sequence the user seeded with `sequence`, seeded from `sequence_rng` so that the same sequence gives the same rolls.
+    pub fn from_sequence(sequence_rng: &mut StdRng, sequence: u64) -> (RngSource, StdRng) {
+        let seed = sequence_rng.gen();
+        (RngSource::Seeded { seed, sequence }, StdRng::seed_from_u64(seed))
+    }
+
+    pub fn seed(&self) -> u64 {
+        match self {
+            RngSource::Entropy { seed } | RngSource::Seeded { seed, .. } => *seed,
+        }
+    }
+
+    /// The name of the source, as written in the log: `entropy` or `seeded`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RngSource::Entropy { .. } => "entropy",
+            RngSource::Seeded { .. } => "seeded",
+        }
+    }
+}
+
+/// Which entries to pick out of the log. Every condition which is set must hold.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    /// Only entries from this time on.
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries from before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Only entries rolled under this profile.
+    pub profile: Option<String>,
+    /// Only the last this many of the entries which meet the other conditions.
+    pub last: Option<usize>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        let after_start = match self.since {
+            Some(since) => entry.time >= since,
+            None => true,
+        };
+        let before_end = match self.until {
+            Some(until) => entry.time < until,
+            None => true,
+        };
+        let in_profile = match &self.profile {
+            Some(profile) => entry.profile.as_ref() == Some(profile),
+            None => true,
+        };
+
+        after_start && before_end && in_profile
+    }
+
+    /// The entries which pass the filter, in their original order.
+    pub fn apply(&self, entries: Vec<LogEntry>) -> Vec<LogEntry> {
+        let mut matching: Vec<LogEntry> = entries.into_iter().filter(|entry| self.matches(entry)).collect();
+        if let Some(last) = self.last {
+            let skipped = matching.len().saturating_sub(last);
+            matching.drain(..skipped);
+        }
+
+        matching
+    }
+}
+
+/// The session log file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionLog {
+    path: PathBuf,
+}
+
+impl SessionLog {
+    pub fn new(path: impl Into<PathBuf>) -> SessionLog {
+        SessionLog { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds `entry` to the end of the log, creating the log and its directory if they don't exist yet.
+    pub fn append(&self, entry: &LogEntry) -> Result<(), StorageError> {
+        let mut line = serde_json::to_string(entry).unwrap();
+        line.push('\n');
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent).map_err(|e| self.io_error(e))?;
+        }
+        let appended = OpenOptions::new().create(true).read(true).append(true).open(&self.path).and_then(|mut file| {
+            // A crash can leave the last entry cut short, and this one mustn't be lost by running on from it.
+            if ends_mid_line(&mut file)? {
+                line.insert(0, '\n');
+            }
+            // One write of the whole line, so that entries appended at once by the GUI and CLI can't interleave.
+            file.write_all(line.as_bytes())
+        });
+        appended.map_err(|e| self.io_error(e))
+    }
+
+    /// Every entry in the log, oldest first. There are none if nothing has been logged yet.
+    ///
+    /// A line which can't be read as an entry, such as one cut short by a crash, is skipped rather than hiding the rest of the log.
+    pub fn entries(&self) -> Result<Vec<LogEntry>, StorageError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(self.io_error(e)),
+        };
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            if let Ok(entry) = serde_json::from_str(&line.map_err(|e| self.io_error(e))?) {
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// The entries which pass `filter`, oldest first.
+    pub fn query(&self, filter: &LogFilter) -> Result<Vec<LogEntry>, StorageError> {
+        Ok(filter.apply(self.entries()?))
+    }
+
+    /// The capped history shown by the GUI: the inputs and results of the last `max_entries` rolls.
+    pub fn history(&self, max_entries: u64) -> Result<History, StorageError> {
+        let filter = LogFilter {
+            last: Some(max_entries as usize),
+            ..LogFilter::default()
+        };
+        Ok(self.query(&filter)?.into_iter().map(|entry| (entry.input, entry.result)).collect())
+    }
+
+    /// Starts the log with the rolls in an older history file, if there's no log yet, returning how many were imported.
+    ///
+    /// History files don't record when each roll was made, so every imported entry gets the time the file was last saved.
+    pub fn import_history(&self, store: &HistoryStore) -> Result<usize, StorageError> {
+        if self.path.exists() || !store.path().exists() {
+            return Ok(0);
+        }
+        let time = metadata(store.path()).and_then(|metadata| metadata.modified()).map_err(|e| self.io_error(e))?;
+        let history = store.load()?;
+        for (input, result) in history.iter() {
+            let entry = LogEntry {
+                time: DateTime::from(time),
+                ..LogEntry::new(input.clone(), result.clone(), None)
+            };
+            self.append(&entry)?;
+        }
+
+        Ok(history.len())
+    }
+
+    fn io_error(&self, error: io::Error) -> StorageError {
+        StorageError::Io {
+            path: self.path.clone(),
+            message: error.to_string(),
+        }
+    }
+}
+
+//////////////////////////
+//   Helper functions   //
+//////////////////////////
+
+/// Whether `file` is non-empty and doesn't end with a newline.
+fn ends_mid_line(file: &mut File) -> io::Result<bool> {
+    if file.metadata()?.len() == 0 {
+        return Ok(false);
+    }
+    let mut last = [0u8];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+
+    Ok(last[0] != b'\n')
+}
@@ -0,0 +1,272 @@
+use crate::expr::{BinaryOperator, DiceExpr, PoolModifier};
+use crate::parse::{ArithmeticMode, CritMode, EvaluationContext};
+use crate::rational::Rational;
+use rust_decimal::prelude::*;
+
+/// Rewrites `expression` into a simpler one which rolls the same way under `context`.
+///
+/// Dice-free sub-expressions are folded into numbers, the numbers in a chain of additions and subtractions are combined into one,
+/// and groups of the same dice added with the same sign are merged, as in `1d6 + 1d6` becoming `2d6`.
+/// Anything whose result could differ, such as an attack die losing its natural result, a merged group going over a limit, or a reordered sum overflowing, is left alone.
+pub fn simplify(expression: &DiceExpr, context: &EvaluationContext) -> DiceExpr {
+    match expression {
+        DiceExpr::Number(_) => expression.clone(),
+        DiceExpr::Negate(operand) => negate(simplify(operand, context)),
+        DiceExpr::Binary(BinaryOperator::Plus, _, _) | DiceExpr::Binary(BinaryOperator::Minus, _, _) => simplify_sum(expression, context),
+        DiceExpr::Binary(operator, left, right) => {
+            let (left, right) = (simplify(left, context), simplify(right, context));
+            match (&left, &right) {
+                (DiceExpr::Number(a), DiceExpr::Number(b)) => match fold(*operator, *a, *b, context.arithmetic) {
+                    Some(result) => DiceExpr::Number(result),
+                    None => DiceExpr::Binary(*operator, Box::new(left), Box::new(right)),
+                },
+                _ => DiceExpr::Binary(*operator, Box::new(left), Box::new(right)),
+            }
+        }
+        DiceExpr::Dice(number, sides) => DiceExpr::Dice(Box::new(simplify(number, context)), Box::new(simplify(sides, context))),
+        DiceExpr::NamedDice(number, name) => DiceExpr::NamedDice(Box::new(simplify(number, context)), name.clone()),
+        DiceExpr::Modified(pool, modifier) => {
+            // A pool modifier fails on anything but a dice pool, so simplifying must not turn a non-pool into a pool or back.
+            let simplified = simplify(pool, context);
+            if is_pool(&simplified) == is_pool(pool) {
+                DiceExpr::Modified(Box::new(simplified), modifier.clone())
+            } else {
+                expression.clone()
+            }
+        }
+        DiceExpr::Crit(inner) => {
+            let critical_context = EvaluationContext { critical: true, ..context.clone() };
+            DiceExpr::Crit(Box::new(simplify(inner, &critical_context)))
+        }
+        DiceExpr::Percentile(skill, extra_tens_dice) => DiceExpr::Percentile(Box::new(simplify(skill, context)), *extra_tens_dice),
+    }
+}
+
+//////////////////////////
+//   Helper functions   //
+//////////////////////////
+
+fn negate(expression: DiceExpr) -> DiceExpr {
+    match expression {
+        DiceExpr::Number(number) => DiceExpr::Number(-number),
+        _ => DiceExpr::Negate(Box::new(expression)),
+    }
+}
+
+/// Whether evaluating `expression` gives a dice pool which a pool modifier can apply to.
+fn is_pool(expression: &DiceExpr) -> bool {
+    match expression {
+        DiceExpr::Dice(_, _) | DiceExpr::NamedDice(_, _) => true,
+        DiceExpr::Modified(pool, modifier) => !matches!(modifier, PoolModifier::Count(_)) && is_pool(pool),
+        DiceExpr::Crit(inner) => is_pool(inner),
+        _ => false,
+    }
+}
+
+/// Applies `operator` to two numbers the way evaluation would, or returns `None` if that fails or the result isn't exactly a `Decimal`.
+fn fold(operator: BinaryOperator, a: Decimal, b: Decimal, arithmetic: ArithmeticMode) -> Option<Decimal> {
+    match arithmetic {
+        ArithmeticMode::Decimal => match operator {
+            BinaryOperator::Plus => a.checked_add(b),
+            BinaryOperator::Minus => a.checked_sub(b),
+            BinaryOperator::Times => a.checked_mul(b),
+            BinaryOperator::Divide => a.checked_div(b),
+            BinaryOperator::Mod => a.checked_rem(b),
+        },
+        ArithmeticMode::Rational => {
+            let (a, b) = (Rational::from_decimal(a)?, Rational::from_decimal(b)?);
+            let result = match operator {
+                BinaryOperator::Plus => a.checked_add(b),
+                BinaryOperator::Minus => a.checked_sub(b),
+                BinaryOperator::Times => a.checked_mul(b),
+                BinaryOperator::Divide => a.checked_div(b),
+                BinaryOperator::Mod => a.checked_rem(b),
+            }?;
+            let decimal = result.to_decimal()?;
+            if Rational::from_decimal(decimal)? == result {
+                Some(decimal)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Flattens a chain of additions and subtractions into its terms, each marked with whether it's subtracted.
+///
+/// A sum on the right is worked out before it's added, so it's only flattened along with the rest when `reassociate` is set.
+fn collect_terms(expression: &DiceExpr, subtracted: bool, reassociate: bool, terms: &mut Vec<(bool, DiceExpr)>) {
+    match expression {
+        DiceExpr::Binary(BinaryOperator::Plus, left, right) => {
+            collect_terms(left, subtracted, reassociate, terms);
+            if reassociate {
+                collect_terms(right, subtracted, reassociate, terms);
+            } else {
+                terms.push((subtracted, right.as_ref().clone()));
+            }
+        }
+        DiceExpr::Binary(BinaryOperator::Minus, left, right) => {
+            collect_terms(left, subtracted, reassociate, terms);
+            if reassociate {
+                collect_terms(right, !subtracted, reassociate, terms);
+            } else {
+                terms.push((!subtracted, right.as_ref().clone()));
+            }
+        }
+        _ => terms.push((subtracted, expression.clone())),
+    }
+}
+
+/// What a dice group rolls, for telling whether two groups can be merged.
+#[derive(PartialEq)]
+enum DieKind {
+    Sides(Decimal),
+    Named(String),
+}
+
+/// The count of a dice group if it's a plain integer, along with its kind of die.
+fn dice_group(expression: &DiceExpr) -> Option<(u64, DieKind)> {
+    let (number, kind) = match expression {
+        DiceExpr::Dice(number, sides) => match sides.as_ref() {
+            DiceExpr::Number(sides) if *sides > Decimal::from(0) && *sides == sides.floor() => (number, DieKind::Sides(*sides)),
+            _ => return None,
+        },
+        DiceExpr::NamedDice(number, name) => (number, DieKind::Named(name.clone())),
+        _ => return None,
+    };
+
+    match number.as_ref() {
+        DiceExpr::Number(number) if !number.is_sign_negative() && *number == number.floor() => Some((number.normalize().to_u64()?, kind)),
+        _ => None,
+    }
+}
+
+/// Whether `count` dice of this kind can be rolled as one group without going over a limit which the separate groups didn't.
+fn can_merge(count: u64, kind: &DieKind, context: &EvaluationContext) -> bool {
+    match kind {
+        DieKind::Named(_) => count <= context.limits.max_dice_per_group,
+        DieKind::Sides(sides) => {
+            let summarized = match context.summarize_above {
+                Some(threshold) => count > threshold,
+                None => false,
+            };
+            let rolled = if context.critical && context.crit_mode == CritMode::Double { count.checked_mul(2) } else { Some(count) };
+            let within_limit = match rolled {
+                Some(rolled) => summarized || rolled <= context.limits.max_dice_per_group,
+                None => summarized,
+            };
+            // The merged group's total mustn't overflow where the separate ones' didn't.
+            highest_total(count, *sides, context).is_some() && within_limit
+        }
+    }
+}
+
+/// Whether this group is the attack die, whose natural result would be lost by merging it with others.
+fn is_attack_die(count: u64, kind: &DieKind, context: &EvaluationContext) -> bool {
+    match (kind, context.attack_die_sides) {
+        (DieKind::Sides(sides), Some(attack_sides)) => !context.critical && count == 1 && *sides == Decimal::from(attack_sides),
+        _ => false,
+    }
+}
+
+/// The most a group of `count` dice with this many sides can total, which crits can double, or `None` if that overflows.
+fn highest_total(count: u64, sides: Decimal, context: &EvaluationContext) -> Option<Decimal> {
+    let highest = Decimal::from(count).checked_mul(sides)?;
+    if context.critical {
+        highest.checked_mul(Decimal::from(2))
+    } else {
+        Some(highest)
+    }
+}
+
+/// The most a term can add to or take from a running total, if that's known.
+fn magnitude(term: &DiceExpr, context: &EvaluationContext) -> Option<Decimal> {
+    match term {
+        DiceExpr::Number(number) => Some(number.abs()),
+        _ => match dice_group(term)? {
+            (count, DieKind::Sides(sides)) => highest_total(count, sides, context),
+            (_, DieKind::Named(_)) => None,
+        },
+    }
+}
+
+fn with_count(expression: &DiceExpr, count: u64) -> DiceExpr {
+    match expression {
+        DiceExpr::Dice(_, sides) => DiceExpr::Dice(Box::new(DiceExpr::Number(Decimal::from(count))), sides.clone()),
+        DiceExpr::NamedDice(_, name) => DiceExpr::NamedDice(Box::new(DiceExpr::Number(Decimal::from(count))), name.clone()),
+        _ => unreachable!("Called with_count on something other than a dice group."),
+    }
+}
+
+/// Simplifies a chain of additions and subtractions, keeping its terms in order.
+///
+/// When the terms are small enough that no running total could overflow, numbers are combined into the position of the first one,
+/// and dice groups merged into the first earlier group of the same dice, whatever lies between. Otherwise only neighbouring terms
+/// added with the same sign are combined, so every running total lies between ones the original passes through.
+fn simplify_sum(expression: &DiceExpr, context: &EvaluationContext) -> DiceExpr {
+    let mut in_order = Vec::new();
+    collect_terms(expression, false, false, &mut in_order);
+    let in_order: Vec<(bool, DiceExpr)> = in_order.into_iter().map(|(subtracted, term)| (subtracted, simplify(&term, context))).collect();
+    let mut flattened = Vec::new();
+    for (subtracted, term) in in_order.iter() {
+        collect_terms(term, *subtracted, true, &mut flattened);
+    }
+    let reorder = flattened.iter().try_fold(Decimal::from(0), |bound, (_, term)| bound.checked_add(magnitude(term, context)?)).is_some();
+    let terms = if reorder { flattened } else { in_order };
+
+    let mut simplified: Vec<(bool, DiceExpr)> = Vec::new();
+    let mut first_constant = None;
+    for (subtracted, term) in terms {
+        if let DiceExpr::Number(number) = term {
+            // Numbers are kept with their signs folded in.
+            let number = if subtracted { -number } else { number };
+            let position = match (reorder, simplified.last()) {
+                (true, _) => first_constant,
+                (false, Some((_, DiceExpr::Number(total)))) if simplified.len() == 1 || total.is_sign_negative() == number.is_sign_negative() => Some(simplified.len() - 1),
+                (false, _) => None,
+            };
+            let combined = position.and_then(|position| match simplified[position].1 {
+                DiceExpr::Number(total) => Some((position, fold(BinaryOperator::Plus, total, number, context.arithmetic)?)),
+                _ => None,
+            });
+            match combined {
+                Some((position, total)) => simplified[position].1 = DiceExpr::Number(total),
+                None => {
+                    first_constant = first_constant.or(Some(simplified.len()));
+                    simplified.push((false, DiceExpr::Number(number)));
+                }
+            }
+            continue;
+        }
+
+        // A dice group is merged into the first earlier group of the same dice with the same sign, looking back only through the run of such groups just before it unless reordering.
+        let run = simplified.iter().rev().take_while(|(other_subtracted, other)| *other_subtracted == subtracted && dice_group(other).is_some()).count();
+        let earliest = if reorder { 0 } else { simplified.len() - run };
+        let merge = dice_group(&term).and_then(|(count, kind)| {
+            simplified.iter().enumerate().skip(earliest).find_map(|(position, (other_subtracted, other))| {
+                let (other_count, other_kind) = dice_group(other)?;
+                let total = count.checked_add(other_count)?;
+                let mergeable = *other_subtracted == subtracted && other_kind == kind && !is_attack_die(count, &kind, context) && !is_attack_die(other_count, &kind, context) && can_merge(total, &kind, context);
+                if mergeable {
+                    Some((position, total))
+                } else {
+                    None
+                }
+            })
+        });
+        match merge {
+            Some((position, total)) => simplified[position].1 = with_count(&simplified[position].1, total),
+            None => simplified.push((subtracted, term)),
+        }
+    }
+
+    let mut members = simplified.into_iter();
+    let (first_subtracted, first) = members.next().unwrap();
+    let first = if first_subtracted { negate(first) } else { first };
+    members.fold(first, |left, (subtracted, right)| match (subtracted, right) {
+        (false, DiceExpr::Number(number)) if number.is_sign_negative() => DiceExpr::Binary(BinaryOperator::Minus, Box::new(left), Box::new(DiceExpr::Number(-number))),
+        (true, DiceExpr::Number(number)) if number.is_sign_negative() => DiceExpr::Binary(BinaryOperator::Plus, Box::new(left), Box::new(DiceExpr::Number(-number))),
+        (false, right) => DiceExpr::Binary(BinaryOperator::Plus, Box::new(left), Box::new(right)),
+        (true, right) => DiceExpr::Binary(BinaryOperator::Minus, Box::new(left), Box::new(right)),
+    })
+}
@@ -0,0 +1,446 @@
+//! Saved settings, roll history, and shortcuts, shared by the GUI, the CLI, and anything else which wants to read them.
+//!
+//! Settings and shortcuts are kept as JSON, wrapped in an envelope recording the version of its format: `{"version": 1, "data": ...}`.
+//! Files written before versioning hold just the data, and count as version 0; see [`Schema`] for how older files are upgraded.
+//! The data for each file is as follows. Settings go in the config directory:
+//!
+//! - `config.json` holds a [`Config`] object. Fields missing from an older file take their default values.
+//!
+//! And everything else in the data directory:
+//!
+//! - `shortcuts.json` holds an array of `{"name": "...", "roll": "..."}` objects, newest first. Rolls are saved in canonical form when they parse.
+//! - `dice.json` holds custom [`DiceDefinitions`](crate::registry::DiceDefinitions), if there are any.
+//! - `sessions.jsonl` is the append-only log of every roll; see [`session`](crate::session) for its format.
+//! - `history.json` holds an array of `[input, result]` pairs, as kept before the session log. It's now only read, to start the log with.
+//! - `repl_history.txt` holds the lines typed into the CLI's interactive mode, one per line.
+//!
+//! [`DataDir::locate`] decides where those directories are. A file which doesn't exist yet loads as its store's default, so a fresh directory needs no setup.
+//!
+//! Each save first moves the previous version into a backup, so `shortcuts.json.bak.1` is the version before the current one, `shortcuts.json.bak.2` the one before that,
+//! and so on up to [`BACKUP_COUNT`]. A file which can't be parsed is copied to `shortcuts.json.corrupt` (or `.corrupt.1`, and so on) so that it's never lost.
+
+use crate::error::StorageError;
+use crate::limits::ResourceLimits;
+use crate::parse::{ArithmeticMode, CritMode, EvaluationContext, RollInformation};
+use crate::precision::DisplayPolicy;
+use crate::session::SessionLog;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env::{current_exe, var_os};
+use std::fs::{copy, create_dir_all, read_to_string, rename, write, File};
+use std::io::{self, ErrorKind, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// A file which, placed beside the executable, keeps everything in `fluorite_data` beside it too.
+pub const PORTABLE_MARKER: &str = "fluorite.portable";
+
+/// How many earlier versions of each file are kept as backups.
+pub const BACKUP_COUNT: usize = 3;
+
+/// User settings, as saved in `config.json`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Config {
+    pub max_history_entries: u64,
+    pub save_history: bool,
+    pub save_shortcuts: bool,
+    #[serde(default)]
+    pub crit_mode: CritMode,
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    #[serde(default = "default_summarize_above")]
+    pub summarize_above: Option<u64>,
+    #[serde(default)]
+    pub arithmetic: ArithmeticMode,
+    #[serde(default)]
+    pub display: DisplayPolicy,
+    /// The profile recorded with each roll in the session log, such as the name of the character being played.
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            max_history_entries: 100,
+            save_history: true,
+            save_shortcuts: true,
+            crit_mode: CritMode::Double,
+            limits: ResourceLimits::new(),
+            summarize_above: default_summarize_above(),
+            arithmetic: ArithmeticMode::Decimal,
+            display: DisplayPolicy::new(),
+            profile: None,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
+}
+
+/// A named roll, which can be rolled again without retyping it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct RollShortcut {
+    pub name: String,
+    pub roll: String,
+}
+
+/// Past rolls, oldest first: each input along with its result or the error it produced.
+///
+/// Saved as an array of `[input, result]` pairs, where `result` is `{"Ok": <RollInformation>}` or `{"Err": "<message>"}`.
+pub type History = Vec<(String, Result<RollInformation, String>)>;
+
+/// Saved shortcuts, newest first.
+pub type Shortcuts = Vec<RollShortcut>;
+
+/// A type which can be kept in a [`Store`], along with how to bring files saved in older versions of its format up to date.
+pub trait Schema: Default + DeserializeOwned + Serialize {
+    /// The version of the format which this type reads and writes. Files from before versioning are version 0.
+    const VERSION: u64;
+
+    /// Rewrites `data`, as saved in format `version`, into format `version + 1`.
+    ///
+    /// Version 1 only added the envelope around the data, which loading unwraps before migrating, so there's nothing to rewrite yet.
+    fn migrate(_version: u64, data: Value) -> Result<Value, String> {
+        Ok(data)
+    }
+}
+
+impl Schema for Config {
+    const VERSION: u64 = 1;
+}
+
+impl Schema for History {
+    const VERSION: u64 = 1;
+}
+
+impl Schema for Shortcuts {
+    const VERSION: u64 = 1;
+}
+
+/// The outermost object of each saved file, recording which version of the format its data is in.
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u64,
+    data: &'a T,
+}
+
+/// Why a file's contents couldn't be turned into a value.
+enum Decoding {
+    Invalid(String),
+    Newer(u64),
+}
+
+/// One JSON file holding a value of type `T`.
+#[derive(Clone, Debug)]
+pub struct Store<T> {
+    path: PathBuf,
+    contents: PhantomData<fn() -> T>,
+}
+
+impl<T: Schema> Store<T> {
+    pub fn new(path: impl Into<PathBuf>) -> Store<T> {
+        Store {
+            path: path.into(),
+            contents: PhantomData,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the saved value, or the default if nothing has been saved yet.
+    ///
+    /// A file in an older format is migrated, and rewritten in the current format with its original kept beside it as, for example, `shortcuts.json.v0`.
+    /// A file in a newer format than this version understands is left alone, with [`StorageError::NewerVersion`] returned.
+    ///
+    /// If the file can't be parsed, a copy of it is kept beside it before returning [`StorageError::Corrupt`],
+    /// so that saving over it afterwards can't lose whatever it held. [`Store::restore_backup`] can then recover an earlier version.
+    pub fn load(&self) -> Result<T, StorageError> {
+        let json = match read_to_string(&self.path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(T::default()),
+            Err(e) => return Err(self.io_error(e)),
+        };
+        match Store::decode(&json) {
+            Ok((value, version)) => {
+                if version < T::VERSION {
+                    // Reading still works if the upgraded file can't be written, as in a read-only directory.
+                    let _ = self.upgrade(&json, version, &value);
+                }
+                Ok(value)
+            }
+            Err(Decoding::Newer(version)) => Err(StorageError::NewerVersion {
+                path: self.path.clone(),
+                version,
+                supported: T::VERSION,
+            }),
+            Err(Decoding::Invalid(message)) => Err(StorageError::Corrupt {
+                path: self.path.clone(),
+                message,
+                kept: self.keep_corrupt(&json).map_err(|e| self.io_error(e))?,
+            }),
+        }
+    }
+
+    /// Writes `value` without ever leaving a half-written file, creating the directory if it doesn't exist yet.
+    ///
+    /// The new contents go to a temporary file which then replaces the old one, and the old one becomes the newest of the backups.
+    pub fn save(&self, value: &T) -> Result<(), StorageError> {
+        let json = serde_json::to_string(&Envelope { version: T::VERSION, data: value }).unwrap();
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent).map_err(|e| self.io_error(e))?;
+        }
+
+        let temporary = self.sibling("tmp");
+        let written = File::create(&temporary).and_then(|mut file| {
+            file.write_all(json.as_bytes())?;
+            file.sync_all()
+        });
+        written.and_then(|_| self.rotate_backups()).and_then(|_| rename(&temporary, &self.path)).map_err(|e| self.io_error(e))
+    }
+
+    /// Where the original of a file upgraded from format `version` is kept.
+    pub fn original_path(&self, version: u64) -> PathBuf {
+        self.sibling(&format!("v{}", version))
+    }
+
+    /// The most recent backup which can still be read, if there is one.
+    pub fn restore_backup(&self) -> Option<T> {
+        (1..=BACKUP_COUNT).find_map(|generation| {
+            let json = read_to_string(self.backup_path(generation)).ok()?;
+            Store::<T>::decode(&json).ok().map(|(value, _)| value)
+        })
+    }
+
+    /// Where the backup from `generation` saves ago is kept, counting from 1 for the most recent.
+    pub fn backup_path(&self, generation: usize) -> PathBuf {
+        self.sibling(&format!("bak.{}", generation))
+    }
+
+    /// Parses a file's contents, whatever version of the format they're in, into a value along with the version they were in.
+    fn decode(json: &str) -> Result<(T, u64), Decoding> {
+        let document: Value = serde_json::from_str(json).map_err(|e| Decoding::Invalid(e.to_string()))?;
+        let (version, mut data) = split_envelope(document);
+        if version > T::VERSION {
+            return Err(Decoding::Newer(version));
+        }
+        for from in version..T::VERSION {
+            data = T::migrate(from, data).map_err(Decoding::Invalid)?;
+        }
+        let value = serde_json::from_value(data).map_err(|e| Decoding::Invalid(e.to_string()))?;
+
+        Ok((value, version))
+    }
+
+    /// Rewrites a file saved in an older format `version` in the current one, keeping its original `json` first.
+    fn upgrade(&self, json: &str, version: u64, value: &T) -> Result<(), StorageError> {
+        let original = self.original_path(version);
+        if !original.exists() {
+            write(&original, json).map_err(|e| self.io_error(e))?;
+        }
+        self.save(value)
+    }
+
+    /// The file named like this store's with `extension` added, as `shortcuts.json.tmp` is to `shortcuts.json`.
+    fn sibling(&self, extension: &str) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(extension);
+        self.path.with_file_name(name)
+    }
+
+    /// Shifts each backup one generation older, dropping the oldest, then copies the current file in as the newest.
+    fn rotate_backups(&self) -> io::Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+        for generation in (1..BACKUP_COUNT).rev() {
+            let backup = self.backup_path(generation);
+            if backup.exists() {
+                rename(&backup, self.backup_path(generation + 1))?;
+            }
+        }
+        copy(&self.path, self.backup_path(1)).map(|_| ())
+    }
+
+    /// Copies unparseable `contents` to the first free `.corrupt` file beside this store's, unless one already holds them.
+    fn keep_corrupt(&self, contents: &str) -> io::Result<PathBuf> {
+        let mut attempt = 0;
+        loop {
+            let kept = match attempt {
+                0 => self.sibling("corrupt"),
+                _ => self.sibling(&format!("corrupt.{}", attempt)),
+            };
+            match read_to_string(&kept) {
+                Ok(existing) if existing == contents => return Ok(kept),
+                Ok(_) => attempt += 1,
+                Err(e) if e.kind() == ErrorKind::NotFound => return write(&kept, contents).map(|_| kept),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn io_error(&self, error: io::Error) -> StorageError {
+        StorageError::Io {
+            path: self.path.clone(),
+            message: error.to_string(),
+        }
+    }
+}
+
+pub type ConfigStore = Store<Config>;
+pub type HistoryStore = Store<History>;
+pub type ShortcutsStore = Store<Shortcuts>;
+
+/// Where the stores are kept: a config directory for settings, and a data directory for everything else.
+///
+/// In portable mode, or when a directory is given explicitly, both are the same directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataDir {
+    config: PathBuf,
+    data: PathBuf,
+}
+
+impl DataDir {
+    /// Keeps everything in the single directory `path`.
+    pub fn new(path: impl Into<PathBuf>) -> DataDir {
+        let path = path.into();
+        DataDir { config: path.clone(), data: path }
+    }
+
+    /// Finds where to keep everything. In order of preference, that's:
+    ///
+    /// 1. `explicit`, as given by `--data-dir`.
+    /// 2. The `fluorite_data` directory beside the executable, in portable mode. That's when a file named `fluorite.portable` sits beside the executable,
+    ///    or when `fluorite_data` already exists there, as it does for anyone upgrading from a version which always kept its data there.
+    /// 3. `fluorite` within `$XDG_CONFIG_HOME` (default `~/.config`) for settings, and within `$XDG_DATA_HOME` (default `~/.local/share`) for everything else.
+    ///    On Windows, both are `fluorite` within `%APPDATA%`.
+    pub fn locate(explicit: Option<PathBuf>) -> Result<DataDir, StorageError> {
+        if let Some(path) = explicit {
+            return Ok(DataDir::new(path));
+        }
+        if let Ok(portable) = DataDir::beside_executable() {
+            if portable.data.exists() || portable.data.with_file_name(PORTABLE_MARKER).exists() {
+                return Ok(portable);
+            }
+        }
+        DataDir::per_user()
+    }
+
+    /// The `fluorite_data` directory next to the running executable, used in portable mode.
+    pub fn beside_executable() -> Result<DataDir, StorageError> {
+        let mut path = current_exe().map_err(|e| StorageError::Io {
+            path: PathBuf::from("fluorite_data"),
+            message: format!("couldn't find the running executable: {}", e),
+        })?;
+        path.pop();
+        path.push("fluorite_data");
+
+        Ok(DataDir::new(path))
+    }
+
+    /// The current user's own directories, following the XDG base directory specification outside Windows.
+    #[cfg(not(windows))]
+    pub fn per_user() -> Result<DataDir, StorageError> {
+        let config = base_directory("XDG_CONFIG_HOME", ".config")?;
+        let data = base_directory("XDG_DATA_HOME", ".local/share")?;
+
+        Ok(DataDir {
+            config: config.join("fluorite"),
+            data: data.join("fluorite"),
+        })
+    }
+
+    /// The current user's own directories, within the roaming application data folder on Windows.
+    #[cfg(windows)]
+    pub fn per_user() -> Result<DataDir, StorageError> {
+        match absolute_variable("APPDATA") {
+            Some(path) => Ok(DataDir::new(path.join("fluorite"))),
+            None => Err(StorageError::Io {
+                path: PathBuf::from("%APPDATA%"),
+                message: String::from("APPDATA isn't set, so there's nowhere to keep settings; try --data-dir"),
+            }),
+        }
+    }
+
+    /// The directory holding `config.json`.
+    pub fn config_path(&self) -> &Path {
+        &self.config
+    }
+
+    /// The directory holding everything other than `config.json`.
+    pub fn data_path(&self) -> &Path {
+        &self.data
+    }
+
+    pub fn config(&self) -> ConfigStore {
+        Store::new(self.config.join("config.json"))
+    }
+
+    pub fn history(&self) -> HistoryStore {
+        Store::new(self.data.join("history.json"))
+    }
+
+    pub fn shortcuts(&self) -> ShortcutsStore {
+        Store::new(self.data.join("shortcuts.json"))
+    }
+
+    pub fn session_log(&self) -> SessionLog {
+        SessionLog::new(self.data.join("sessions.jsonl"))
+    }
+
+    pub fn dice_path(&self) -> PathBuf {
+        self.data.join("dice.json")
+    }
+
+    pub fn repl_history_path(&self) -> PathBuf {
+        self.data.join("repl_history.txt")
+    }
+}
+
+//////////////////////////
+//   Helper functions   //
+//////////////////////////
+
+fn default_summarize_above() -> Option<u64> {
+    EvaluationContext::new().summarize_above
+}
+
+/// Separates a saved document into its format version and its data. Anything other than an envelope is data from before versioning.
+fn split_envelope(document: Value) -> (u64, Value) {
+    match document {
+        Value::Object(mut fields) if fields.len() == 2 && fields.contains_key("data") => match fields.get("version").and_then(Value::as_u64) {
+            Some(version) => (version, fields.remove("data").unwrap()),
+            None => (0, Value::Object(fields)),
+        },
+        document => (0, document),
+    }
+}
+
+/// An environment variable holding a directory, ignored unless it's an absolute path as the XDG specification requires.
+fn absolute_variable(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(var_os(name)?);
+    match path.is_absolute() {
+        true => Some(path),
+        false => None,
+    }
+}
+
+/// An XDG base directory: the path in `variable` if it's set, otherwise `fallback` within the home directory.
+#[cfg(not(windows))]
+fn base_directory(variable: &str, fallback: &str) -> Result<PathBuf, StorageError> {
+    match (absolute_variable(variable), absolute_variable("HOME")) {
+        (Some(path), _) => Ok(path),
+        (None, Some(home)) => Ok(home.join(fallback)),
+        (None, None) => Err(StorageError::Io {
+            path: PathBuf::from(format!("${}", variable)),
+            message: format!("neither {} nor HOME is set, so there's nowhere to keep settings; try --data-dir", variable),
+        }),
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A tally of narrative symbols, such as the successes and advantages from a Genesys roll.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct SymbolPool(BTreeMap<String, i64>);
+
+impl SymbolPool {
+    pub fn new() -> SymbolPool {
+        SymbolPool(BTreeMap::new())
+    }
+    pub fn add(&mut self, symbol: &str, count: i64) {
+        *self.0.entry(String::from(symbol)).or_insert(0) += count;
+    }
+    pub fn count(&self, symbol: &str) -> i64 {
+        self.0.get(symbol).copied().unwrap_or(0)
+    }
+    pub fn merged(&self, other: &SymbolPool) -> SymbolPool {
+        let mut merged = self.clone();
+        for (symbol, count) in other.0.iter() {
+            merged.add(symbol, *count);
+        }
+        merged
+    }
+    /// Returns the net pool left after each cancelling pair of symbols has removed one another.
+    pub fn cancelled(&self, cancellations: &[Cancellation]) -> SymbolPool {
+        let mut net = self.clone();
+        for cancellation in cancellations {
+            let cancelled = net.count(&cancellation.symbol).min(net.count(&cancellation.cancelled_by));
+            net.add(&cancellation.symbol, -cancelled);
+            net.add(&cancellation.cancelled_by, -cancelled);
+        }
+        net.0.retain(|_, count| *count != 0);
+        net
+    }
+}
+
+impl fmt::Display for SymbolPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbols = self.0.iter().filter(|(_, count)| **count != 0).map(|(symbol, count)| format!("{} {}", count, symbol)).collect::<Vec<String>>();
+        if symbols.is_empty() {
+            write!(f, "no symbols")
+        } else {
+            write!(f, "{}", symbols.join(", "))
+        }
+    }
+}
+
+/// A pair of symbols which cancel one another out one-for-one, such as success and failure.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Cancellation {
+    pub symbol: String,
+    pub cancelled_by: String,
+}
+
+impl Cancellation {
+    pub fn new(symbol: &str, cancelled_by: &str) -> Cancellation {
+        Cancellation {
+            symbol: String::from(symbol),
+            cancelled_by: String::from(cancelled_by),
+        }
+    }
+}
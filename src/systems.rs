@@ -0,0 +1,254 @@
+use crate::error::RollError;
+use crate::limits::{DiceTally, Limit, ResourceLimits};
+use crate::parse::{format_rolls, roll_die, RollInformation};
+use rand::RngCore;
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Tabletop systems with a fixed roll-and-interpret procedure.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum GameSystem {
+    /// Chronicles of Darkness (new World of Darkness): d10 pool, 8+ succeeds, 10-again.
+    Chronicles,
+    /// Shadowrun: d6 pool, 5+ hits, glitches on too many 1s.
+    Shadowrun,
+    /// Blades in the Dark: d6 pool, read the highest die.
+    Blades,
+    /// Powered by the Apocalypse: 2d6 plus a stat.
+    Pbta,
+    /// Ironsworn: d6 action die plus a stat against two d10 challenge dice.
+    Ironsworn,
+    /// Call of Cthulhu 7th edition: d100 graded against a skill.
+    CallOfCthulhu,
+}
+
+impl GameSystem {
+    pub const ALL: [GameSystem; 6] = [GameSystem::Chronicles, GameSystem::Shadowrun, GameSystem::Blades, GameSystem::Pbta, GameSystem::Ironsworn, GameSystem::CallOfCthulhu];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameSystem::Chronicles => "Chronicles of Darkness",
+            GameSystem::Shadowrun => "Shadowrun",
+            GameSystem::Blades => "Blades in the Dark",
+            GameSystem::Pbta => "Powered by the Apocalypse",
+            GameSystem::Ironsworn => "Ironsworn",
+            GameSystem::CallOfCthulhu => "Call of Cthulhu",
+        }
+    }
+
+    /// What the single numeric parameter of [`GameSystem::roll`] means for this system.
+    pub fn parameter_name(&self) -> &'static str {
+        match self {
+            GameSystem::Chronicles | GameSystem::Shadowrun | GameSystem::Blades => "pool size",
+            GameSystem::Pbta | GameSystem::Ironsworn => "modifier",
+            GameSystem::CallOfCthulhu => "skill",
+        }
+    }
+
+    /// Rolls this system's procedure, returning the dice trace with the system's outcome label attached.
+    pub fn roll(&self, parameter: i64) -> Result<RollInformation, RollError> {
+        self.roll_with_limits(parameter, &ResourceLimits::new())
+    }
+
+    pub fn roll_with_limits(&self, parameter: i64, limits: &ResourceLimits) -> Result<RollInformation, RollError> {
+        self.roll_with_rng(parameter, limits, &mut rand::thread_rng())
+    }
+
+    /// Rolls with the given random number generator, so a seeded generator gives the same results every time.
+    pub fn roll_with_rng<R: RngCore>(&self, parameter: i64, limits: &ResourceLimits, rng: &mut R) -> Result<RollInformation, RollError> {
+        let tally = DiceTally::new(limits);
+        match self {
+            GameSystem::Chronicles => roll_chronicles(parameter, &tally, rng),
+            GameSystem::Shadowrun => roll_shadowrun(parameter, &tally, rng),
+            GameSystem::Blades => roll_blades(parameter, &tally, rng),
+            GameSystem::Pbta => Ok(roll_pbta(parameter, rng)),
+            GameSystem::Ironsworn => Ok(roll_ironsworn(parameter, rng)),
+            GameSystem::CallOfCthulhu => Ok(roll_percentile(Decimal::from(parameter), 0, rng)),
+        }
+    }
+}
+
+impl FromStr for GameSystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GameSystem, String> {
+        match s.to_lowercase().as_str() {
+            "chronicles" | "cofd" | "wod" | "nwod" => Ok(GameSystem::Chronicles),
+            "shadowrun" | "sr" => Ok(GameSystem::Shadowrun),
+            "blades" | "bitd" => Ok(GameSystem::Blades),
+            "pbta" | "apocalypse" => Ok(GameSystem::Pbta),
+            "ironsworn" => Ok(GameSystem::Ironsworn),
+            "coc" | "cthulhu" => Ok(GameSystem::CallOfCthulhu),
+            _ => Err(format!("Unknown system \"{}\"; expected one of chronicles, shadowrun, blades, pbta, ironsworn, or coc.", s)),
+        }
+    }
+}
+
+//////////////////////////
+//   Helper functions   //
+//////////////////////////
+
+fn roll_pool(size: i64, sides: i128, rng: &mut dyn RngCore) -> Vec<Decimal> {
+    (0..size).map(|_| roll_die(sides, rng)).collect()
+}
+
+fn count_at_least(rolls: &[Decimal], threshold: i64) -> i64 {
+    rolls.iter().filter(|roll| **roll >= Decimal::from(threshold)).count() as i64
+}
+
+fn check_pool_size(pool: i64, tally: &DiceTally) -> Result<(), RollError> {
+    if pool < 0 {
+        Err(RollError::Evaluation(String::from("Attempted to roll a negative dice pool.")))
+    } else {
+        tally.add(pool as u64)
+    }
+}
+
+/////////////////////////
+//   Roll procedures   //
+/////////////////////////
+
+fn roll_chronicles(pool: i64, tally: &DiceTally, rng: &mut dyn RngCore) -> Result<RollInformation, RollError> {
+    check_pool_size(pool, tally)?;
+
+    if pool == 0 {
+        let chance_die = roll_die(10, rng);
+        let (successes, outcome) = if chance_die == Decimal::from(10) {
+            (1, "Success")
+        } else if chance_die == Decimal::from(1) {
+            (0, "Dramatic failure")
+        } else {
+            (0, "Failure")
+        };
+        return Ok(RollInformation::new(Decimal::from(successes), format!("chance die {}", format_rolls(&[chance_die]))).with_outcome(outcome));
+    }
+
+    let mut rolls = roll_pool(pool, 10, rng);
+    let mut trace = vec![format_rolls(&rolls)];
+    let mut rerolls = rolls.iter().filter(|roll| **roll == Decimal::from(10)).count() as i64;
+    let mut chain = 0;
+    while rerolls > 0 {
+        chain += 1;
+        tally.limits().check(Limit::ExplosionChain, chain)?;
+        tally.add(rerolls as u64)?;
+        let new_rolls = roll_pool(rerolls, 10, rng);
+        trace.push(format!("10-again {}", format_rolls(&new_rolls)));
+        rerolls = new_rolls.iter().filter(|roll| **roll == Decimal::from(10)).count() as i64;
+        rolls.extend(new_rolls);
+    }
+
+    let successes = count_at_least(&rolls, 8);
+    let outcome = match successes {
+        0 => "Failure",
+        1..=4 => "Success",
+        _ => "Exceptional success",
+    };
+
+    Ok(RollInformation::new(Decimal::from(successes), trace.join(", ")).with_outcome(outcome))
+}
+
+fn roll_shadowrun(pool: i64, tally: &DiceTally, rng: &mut dyn RngCore) -> Result<RollInformation, RollError> {
+    check_pool_size(pool, tally)?;
+
+    let rolls = roll_pool(pool, 6, rng);
+    let hits = count_at_least(&rolls, 5);
+    let ones = rolls.iter().filter(|roll| **roll == Decimal::from(1)).count() as i64;
+    let glitch = pool > 0 && ones * 2 > pool;
+
+    let outcome = match (glitch, hits) {
+        (true, 0) => String::from("Critical glitch"),
+        (true, _) => format!("Glitch ({} {})", hits, if hits == 1 { "hit" } else { "hits" }),
+        (false, 0) => String::from("No hits"),
+        (false, 1) => String::from("1 hit"),
+        (false, _) => format!("{} hits", hits),
+    };
+
+    Ok(RollInformation::new(Decimal::from(hits), format_rolls(&rolls)).with_outcome(&outcome))
+}
+
+fn roll_blades(pool: i64, tally: &DiceTally, rng: &mut dyn RngCore) -> Result<RollInformation, RollError> {
+    check_pool_size(pool, tally)?;
+
+    let (rolls, result, sixes) = if pool == 0 {
+        let rolls = roll_pool(2, 6, rng);
+        let lowest = *rolls.iter().min().unwrap();
+        (rolls, lowest, 0) // A zero-dice roll can't crit.
+    } else {
+        let rolls = roll_pool(pool, 6, rng);
+        let highest = *rolls.iter().max().unwrap();
+        let sixes = rolls.iter().filter(|roll| **roll == Decimal::from(6)).count();
+        (rolls, highest, sixes)
+    };
+
+    let outcome = if sixes >= 2 {
+        "Critical success"
+    } else if result == Decimal::from(6) {
+        "Full success"
+    } else if result >= Decimal::from(4) {
+        "Partial success"
+    } else {
+        "Bad outcome"
+    };
+    let processed_string = if pool == 0 { format!("lowest of {}", format_rolls(&rolls)) } else { format_rolls(&rolls) };
+
+    Ok(RollInformation::new(result, processed_string).with_outcome(outcome))
+}
+
+fn roll_pbta(modifier: i64, rng: &mut dyn RngCore) -> RollInformation {
+    let rolls = roll_pool(2, 6, rng);
+    let total = rolls.iter().sum::<Decimal>() + Decimal::from(modifier);
+
+    let outcome = if total >= Decimal::from(10) {
+        "Strong hit"
+    } else if total >= Decimal::from(7) {
+        "Weak hit"
+    } else {
+        "Miss"
+    };
+
+    RollInformation::new(total, format!("{} + {}", format_rolls(&rolls), modifier)).with_outcome(outcome)
+}
+
+fn roll_ironsworn(modifier: i64, rng: &mut dyn RngCore) -> RollInformation {
+    let action_die = roll_die(6, rng);
+    let action_score = (action_die + Decimal::from(modifier)).min(Decimal::from(10));
+    let challenge_dice = roll_pool(2, 10, rng);
+
+    let beaten = challenge_dice.iter().filter(|challenge| action_score > **challenge).count();
+    let outcome = match beaten {
+        2 => "Strong hit",
+        1 => "Weak hit",
+        _ => "Miss",
+    };
+    let outcome = if challenge_dice[0] == challenge_dice[1] { format!("{} with a match", outcome) } else { String::from(outcome) };
+
+    RollInformation::new(action_score, format!("{} + {} vs {}", format_rolls(&[action_die]), modifier, format_rolls(&challenge_dice))).with_outcome(&outcome)
+}
+
+/// Rolls d100 as separate tens and units dice, graded against `skill` by Call of Cthulhu 7th edition rules.
+///
+/// Positive `extra_tens_dice` are bonus dice (keep the lowest result), negative ones penalty dice (keep the highest).
+pub(crate) fn roll_percentile(skill: Decimal, extra_tens_dice: i64, rng: &mut dyn RngCore) -> RollInformation {
+    let tens_dice: Vec<Decimal> = (0..=extra_tens_dice.abs()).map(|_| (roll_die(10, rng) - Decimal::from(1)) * Decimal::from(10)).collect();
+    let units_die = roll_die(10, rng) - Decimal::from(1);
+
+    let results = tens_dice.iter().map(|tens| if tens.is_zero() && units_die.is_zero() { Decimal::from(100) } else { tens + units_die });
+    let result = if extra_tens_dice >= 0 { results.min().unwrap() } else { results.max().unwrap() };
+
+    let fumble_threshold = if skill < Decimal::from(50) { Decimal::from(96) } else { Decimal::from(100) };
+    let outcome = if result == Decimal::from(1) {
+        "Critical success"
+    } else if result >= fumble_threshold {
+        "Fumble"
+    } else if result <= (skill / Decimal::from(5)).floor() {
+        "Extreme success"
+    } else if result <= (skill / Decimal::from(2)).floor() {
+        "Hard success"
+    } else if result <= skill {
+        "Regular success"
+    } else {
+        "Failure"
+    };
+
+    RollInformation::new(result, format!("tens {} units {} -> {}", format_rolls(&tens_dice), format_rolls(&[units_die]), result)).with_outcome(outcome)
+}
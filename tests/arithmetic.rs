@@ -0,0 +1,73 @@
+use fluorite::error::RollError;
+use fluorite::parse::{parse_input, parse_input_with_context, EvaluationContext};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+const MAX_DECIMAL: &str = "79228162514264337593543950335";
+
+fn value_of(input: &str) -> Decimal {
+    parse_input(input).unwrap().value.number().unwrap()
+}
+
+fn assert_evaluation_error(input: &str) {
+    assert_evaluation_error_with_context(input, &EvaluationContext::new());
+}
+
+fn assert_evaluation_error_with_context(input: &str, context: &EvaluationContext) {
+    match parse_input_with_context(input, context) {
+        Err(RollError::Evaluation(_)) => (),
+        other => panic!("Expected an evaluation error for {}, got {:?}", input, other),
+    }
+}
+
+#[test]
+fn ordinary_arithmetic_still_works() {
+    assert_eq!(value_of("7 / 2"), Decimal::from_str("3.5").unwrap());
+    assert_eq!(value_of("7 % 3"), Decimal::from(1));
+    assert_eq!(value_of("6 * 7 - 2 + 1"), Decimal::from(41));
+    assert_eq!(value_of(MAX_DECIMAL), Decimal::MAX);
+}
+
+#[test]
+fn division_by_zero_is_an_error() {
+    assert_evaluation_error("1 / 0");
+    assert_evaluation_error("5 / (2 - 2)");
+    assert_evaluation_error("1d6 / 0");
+}
+
+#[test]
+fn modulo_by_zero_is_an_error() {
+    assert_evaluation_error("1 % 0");
+    assert_evaluation_error("5 % (3 * 0)");
+}
+
+#[test]
+fn multiplication_overflow_is_an_error() {
+    assert_evaluation_error("99999999999999999999 * 99999999999999999999");
+}
+
+#[test]
+fn addition_and_subtraction_overflow_are_errors() {
+    assert_evaluation_error(&format!("{} + 1", MAX_DECIMAL));
+    assert_evaluation_error(&format!("-{} - 1", MAX_DECIMAL));
+}
+
+#[test]
+fn division_overflow_is_an_error() {
+    assert_evaluation_error(&format!("{} / 0.1", MAX_DECIMAL));
+}
+
+#[test]
+fn oversized_literal_is_an_error() {
+    assert_evaluation_error("999999999999999999999999999999999 + 1");
+    assert_evaluation_error("79228162514264337593543950336");
+}
+
+#[test]
+fn doubling_critical_dice_overflow_is_an_error() {
+    let context = EvaluationContext {
+        summarize_above: None,
+        ..EvaluationContext::new()
+    };
+    assert_evaluation_error_with_context(&format!("crit({}d6)", MAX_DECIMAL), &context);
+}
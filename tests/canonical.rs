@@ -0,0 +1,74 @@
+use fluorite::expr::{BinaryOperator, DiceExpr, PoolModifier};
+use fluorite::parse::parse;
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+
+fn canonical(input: &str) -> String {
+    parse(input).unwrap().to_string()
+}
+
+#[test]
+fn messy_input_is_tidied() {
+    assert_eq!(canonical("(2 + 3   ) * 24d(((9))+-(2))"), "(2 + 3) * 24d(9 + -2)");
+    assert_eq!(canonical("  1d20+5-( 2 )"), "1d20 + 5 - 2");
+    assert_eq!(canonical("3*(2*4)"), "3 * (2 * 4)");
+    assert_eq!(canonical("(3*2)*4"), "3 * 2 * 4");
+    assert_eq!(canonical("-(3)d6"), "-3d6");
+}
+
+#[test]
+fn postfix_letters_do_not_run_into_die_names() {
+    assert_eq!(canonical("(2dboost)s"), "(2dboost)s");
+    assert_eq!(canonical("(coc(50)b)d6"), "(coc(50)b)d6");
+    assert_eq!(canonical("coc(50)b1"), "coc(50)b");
+}
+
+fn number() -> impl Strategy<Value = Decimal> {
+    (-1000i64..1000, 0u32..4).prop_map(|(mantissa, scale)| Decimal::new(mantissa, scale))
+}
+
+fn operator() -> impl Strategy<Value = BinaryOperator> {
+    prop_oneof![Just(BinaryOperator::Plus), Just(BinaryOperator::Minus), Just(BinaryOperator::Times), Just(BinaryOperator::Divide), Just(BinaryOperator::Mod)]
+}
+
+fn modifier() -> impl Strategy<Value = PoolModifier> {
+    prop_oneof![
+        Just(PoolModifier::Sort),
+        Just(PoolModifier::Unique),
+        Just(PoolModifier::Matching),
+        (0i64..100, 0u32..2).prop_map(|(mantissa, scale)| PoolModifier::Count(Decimal::new(mantissa, scale))),
+    ]
+}
+
+/// Expressions as the parser builds them, so never a negated number literal.
+fn expression() -> impl Strategy<Value = DiceExpr> {
+    number().prop_map(DiceExpr::Number).prop_recursive(4, 24, 2, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|operand| match operand {
+                DiceExpr::Number(number) => DiceExpr::Number(-number),
+                _ => DiceExpr::Negate(Box::new(operand)),
+            }),
+            (operator(), inner.clone(), inner.clone()).prop_map(|(operator, left, right)| DiceExpr::Binary(operator, Box::new(left), Box::new(right))),
+            (inner.clone(), inner.clone()).prop_map(|(number, sides)| DiceExpr::Dice(Box::new(number), Box::new(sides))),
+            (inner.clone(), "[a-z]{1,6}").prop_map(|(number, name)| DiceExpr::NamedDice(Box::new(number), name)),
+            (inner.clone(), modifier()).prop_map(|(pool, modifier)| DiceExpr::Modified(Box::new(pool), modifier)),
+            inner.clone().prop_map(|inner| DiceExpr::Crit(Box::new(inner))),
+            (inner, -3i64..=3).prop_map(|(skill, extra_tens_dice)| DiceExpr::Percentile(Box::new(skill), extra_tens_dice)),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn formatting_then_parsing_gives_the_same_expression(expression in expression()) {
+        let formatted = expression.to_string();
+        let reparsed = parse(&formatted).unwrap();
+        prop_assert_eq!(reparsed.expression(), &expression, "formatted as {}", formatted);
+    }
+
+    #[test]
+    fn canonical_form_is_stable(expression in expression()) {
+        let formatted = expression.to_string();
+        prop_assert_eq!(parse(&formatted).unwrap().to_string(), formatted);
+    }
+}
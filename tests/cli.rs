@@ -0,0 +1,356 @@
+use fluorite::parse::{parse, EvaluationContext};
+use fluorite::session::RngSource;
+use fluorite::storage::DataDir;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::env::temp_dir;
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// What a run of the CLI printed, and its exit status.
+struct Run {
+    status: i32,
+    stdout: String,
+    stderr: String,
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let path = temp_dir().join(format!("fluorite-cli-test-{}-{}", name, std::process::id()));
+    let _ = remove_dir_all(&path);
+    path
+}
+
+/// Runs the CLI with its own data directory, feeding it `stdin`.
+fn fluorite(data_dir: &Path, args: &[&str], stdin: &str) -> Run {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fluorite"))
+        .arg("--data-dir")
+        .arg(data_dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    Run {
+        status: output.status.code().unwrap(),
+        stdout: String::from_utf8(output.stdout).unwrap(),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+    }
+}
+
+fn lines(text: &str) -> Vec<&str> {
+    text.lines().collect()
+}
+
+#[test]
+fn the_repl_rolls_each_line_until_it_quits() {
+    let data_dir = scratch_dir("repl");
+    let run = fluorite(&data_dir, &["repl"], "2 + 3\n\n1d1 * 4\n:quit\n7\n");
+
+    assert_eq!(run.status, 0);
+    assert_eq!(lines(&run.stdout), ["Type :help for help, or :quit to leave.", "5", "4"]);
+    assert_eq!(lines(&read_to_string(data_dir.join("repl_history.txt")).unwrap()).last(), Some(&":quit"));
+}
+
+#[test]
+fn verbose_toggles_the_full_results() {
+    let run = fluorite(&scratch_dir("repl-verbose"), &["--no-log", "repl"], ":verbose\n1d1+1\n:verbose\n1d1+1\n");
+
+    assert_eq!(lines(&run.stdout)[1..], ["Verbose output on.", "Input: 1d1 + 1", "Rolled: [1] + 1", "Result: 2", "Verbose output off.", "2"]);
+}
+
+#[test]
+fn a_seed_makes_rolls_repeatable() {
+    let data_dir = scratch_dir("repl-seed");
+    let rolls = "1d1000\n1d1000\n1d1000\n";
+    let seeded = fluorite(&data_dir, &["--no-log", "repl"], &format!(":seed 42\n{}:seed 42\n{}", rolls, rolls));
+    let results = lines(&seeded.stdout);
+
+    assert_eq!(results[1], "Rolling with seed 42.");
+    assert_eq!(results[2..5], results[6..9]);
+    assert_eq!(lines(&fluorite(&data_dir, &["--no-log", "repl"], &format!(":seed 42\n{}", rolls)).stdout)[2..], results[2..5]);
+
+    let reseeded = fluorite(&data_dir, &["--no-log", "repl"], ":seed 42\n:seed\n:seed nope\n");
+    assert_eq!(lines(&reseeded.stdout)[2], "Rolling with fresh randomness.");
+    assert!(reseeded.stderr.starts_with("Seeds are whole numbers"));
+}
+
+#[test]
+fn dist_shows_how_often_each_result_comes_up() {
+    let data_dir = scratch_dir("repl-dist");
+    let run = fluorite(&data_dir, &["--no-log", "repl"], ":seed 7\n:dist 1d2 + 1\n");
+    let output = lines(&run.stdout);
+    let rows = &output[2..4];
+
+    assert!(rows[0].starts_with("2  ") && rows[1].starts_with("3  "));
+    let percent = |row: &str| row.split_whitespace().nth(1).unwrap().trim_end_matches('%').parse::<f64>().unwrap();
+    assert!((percent(rows[0]) + percent(rows[1]) - 100.0).abs() < 0.001);
+    assert!(output[4].starts_with("Mean 2.") && output[4].ends_with(" over 10000 rolls, from 2 to 3."));
+    assert_eq!(output.len(), 5);
+    // The same seed gives the same distribution.
+    assert_eq!(fluorite(&data_dir, &["--no-log", "repl"], ":seed 7\n:dist 1d2 + 1\n").stdout, run.stdout);
+}
+
+#[test]
+fn dist_reports_results_too_far_apart_to_group() {
+    let max = Decimal::MAX;
+    let run = fluorite(&scratch_dir("repl-dist-range"), &["--no-log", "repl"], &format!(":dist (1d60 - 30.5) / 29.5 * {}\n:dist {} - 1d50\n", max, max));
+
+    assert_eq!(run.status, 0);
+    assert_eq!(run.stderr, "The results are too far apart to group into ranges.\n");
+    assert!(lines(&run.stdout).last().unwrap().ends_with(&format!(" over 10000 rolls, from {} to {}.", max - Decimal::from(50), max - Decimal::from(1))));
+}
+
+#[test]
+fn bad_lines_and_commands_are_reported_without_leaving() {
+    let run = fluorite(&scratch_dir("repl-errors"), &["--no-log", "repl"], ":bogus\n(\n:dist (\n1/0\n2\n");
+
+    assert_eq!(run.status, 0);
+    assert_eq!(lines(&run.stdout)[1..], ["2"]);
+    assert!(run.stderr.starts_with("Unknown command \":bogus\"; type :help for a list."));
+    assert!(run.stderr.contains("divide by zero"));
+}
+
+fn json_records(data_dir: &Path, format: &str, stdin: &str) -> Vec<Value> {
+    let run = fluorite(data_dir, &["--no-log", "--format", format], stdin);
+    match format {
+        "json" => serde_json::from_str(&run.stdout).unwrap(),
+        _ => run.stdout.lines().map(|line| serde_json::from_str(line).unwrap()).collect(),
+    }
+}
+
+#[test]
+fn json_prints_one_array_of_records() {
+    let records = json_records(&scratch_dir("json"), "json", "2d1 + 2\n10/4\n1/0\n");
+
+    assert_eq!(
+        records,
+        [
+            json!({"input": "2d1 + 2", "canonical": "2d1 + 2", "simplified": null, "trace": "[1, 1] + 2", "value": "4", "display": "4", "outcome": null, "error": null}),
+            json!({"input": "10/4", "canonical": "10 / 4", "simplified": null, "trace": "10 / 4", "value": "2.50", "display": "2.5", "outcome": null, "error": null}),
+            json!({"input": "1/0", "canonical": "1 / 0", "simplified": null, "trace": null, "value": null, "display": null, "outcome": null, "error": {"kind": "evaluation", "message": "Attempted to divide by zero in 1 / 0."}}),
+        ]
+    );
+}
+
+#[test]
+fn ndjson_prints_the_same_records_one_per_line() {
+    let data_dir = scratch_dir("ndjson");
+    let input = "2d1 + 2\n(\n3 * 1d1\n";
+
+    assert_eq!(json_records(&data_dir, "ndjson", input), json_records(&data_dir, "json", input));
+    assert_eq!(json_records(&data_dir, "ndjson", input)[1]["error"]["kind"], "syntax");
+}
+
+#[test]
+fn system_records_carry_their_outcome() {
+    let run = fluorite(&scratch_dir("json-system"), &["--no-log", "--format", "json", "system", "pbta", "--modifier", "-1"], "");
+    let records: Vec<Value> = serde_json::from_str(&run.stdout).unwrap();
+    assert_eq!(records.len(), 1);
+    assert!(["Strong hit", "Weak hit", "Miss"].contains(&records[0]["outcome"].as_str().unwrap()));
+    assert_eq!(records[0]["input"], "Powered by the Apocalypse -1");
+}
+
+#[test]
+fn csv_prints_a_header_and_quotes_fields_that_need_it() {
+    let run = fluorite(&scratch_dir("csv"), &["--no-log", "--format", "csv", "--arithmetic", "rational"], "2d1 + 2\n10/4\n1/0\n");
+
+    assert_eq!(
+        lines(&run.stdout),
+        [
+            "input,canonical,simplified,trace,value,display,outcome,error_kind,error_message",
+            "2d1 + 2,2d1 + 2,,\"[1, 1] + 2\",4,4,,,",
+            "10/4,10 / 4,,10 / 4,5/2,2.5,,,",
+            "1/0,1 / 0,,,,,,evaluation,Attempted to divide by zero in 1 / 0.",
+        ]
+    );
+}
+
+#[test]
+fn plain_and_verbose_print_for_people() {
+    let data_dir = scratch_dir("plain");
+
+    assert_eq!(fluorite(&data_dir, &["--no-log"], "2d1 + 2\n10/3\n").stdout, "4\n3.3333\n");
+    assert_eq!(fluorite(&data_dir, &["--no-log", "--format", "verbose"], "2d1 + 2\n").stdout, "Input: 2d1 + 2\nRolled: [1, 1] + 2\nResult: 4\n");
+    assert_eq!(fluorite(&data_dir, &["--no-log", "--verbose", "2d1"], "").stdout, "Input: 2d1\nRolled: [1, 1]\nResult: 2\n");
+}
+
+#[test]
+fn each_kind_of_failure_has_its_own_exit_status() {
+    let data_dir = scratch_dir("exit-codes");
+    let status = |args: &[&str], stdin: &str| fluorite(&data_dir, args, stdin).status;
+
+    assert_eq!(status(&["--no-log"], "2d6 + 1\n"), 0);
+    assert_eq!(status(&["--no-log", "--bogus"], ""), 1);
+    assert_eq!(status(&["--no-log", "run", "nope"], ""), 1);
+    assert_eq!(status(&["--no-log", "system", "pbta"], ""), 1);
+    assert_eq!(status(&["--no-log"], "(\n"), 2);
+    assert_eq!(status(&["--no-log"], "1d6 + x\n"), 2);
+    assert_eq!(status(&["--no-log"], "1/0\n"), 3);
+    assert_eq!(status(&["--no-log"], "999999999999d6\n"), 3);
+    let missing = data_dir.join("missing.json");
+    assert_eq!(status(&["--no-log", "--dice-file", missing.to_str().unwrap()], "1d6\n"), 4);
+}
+
+#[test]
+fn errors_are_reported_and_the_rest_of_the_lines_rolled() {
+    let run = fluorite(&scratch_dir("continue"), &["--no-log"], "1/0\n(\n2\n");
+
+    assert_eq!(run.stdout, "2\n");
+    assert!(run.stderr.starts_with("fluorite: evaluation error in \"1/0\": Attempted to divide by zero in 1 / 0.\nfluorite: syntax error in \"(\":\n"));
+    assert!(!run.stderr.contains("panicked"));
+    // The first failure decides the exit status.
+    assert_eq!(run.status, 3);
+    assert_eq!(fluorite(&scratch_dir("continue"), &["--no-log"], "(\n1/0\n").status, 2);
+}
+
+#[test]
+fn fail_fast_stops_at_the_first_failure() {
+    let run = fluorite(&scratch_dir("fail-fast"), &["--no-log", "--fail-fast", "--format", "ndjson"], "1\n(\n2\n");
+
+    assert_eq!(run.status, 2);
+    assert_eq!(lines(&run.stdout).len(), 2);
+    assert!(!run.stdout.contains("\"input\":\"2\""));
+}
+
+fn saved_shortcuts(data_dir: &Path) -> Vec<(String, String)> {
+    DataDir::new(data_dir.to_path_buf()).shortcuts().load().unwrap().into_iter().map(|shortcut| (shortcut.name, shortcut.roll)).collect()
+}
+
+fn pair(name: &str, roll: &str) -> (String, String) {
+    (String::from(name), String::from(roll))
+}
+
+#[test]
+fn shortcuts_are_added_renamed_and_deleted_in_the_shared_file() {
+    let data_dir = scratch_dir("shortcuts");
+    let shortcuts = |args: &[&str]| fluorite(&data_dir, &[&["shortcuts"], args].concat(), "").status;
+
+    assert_eq!(shortcuts(&["add", "Longsword attack", "1d20+5"]), 0);
+    assert_eq!(shortcuts(&["add", "Damage", "1d8 + 3"]), 0);
+    // Newest first, with the roll in canonical form.
+    assert_eq!(saved_shortcuts(&data_dir), [pair("Damage", "1d8 + 3"), pair("Longsword attack", "1d20 + 5")]);
+
+    assert_eq!(shortcuts(&["rename", "Damage", "Longsword damage"]), 0);
+    assert_eq!(shortcuts(&["delete", "Longsword attack"]), 0);
+    assert_eq!(saved_shortcuts(&data_dir), [pair("Longsword damage", "1d8 + 3")]);
+}
+
+#[test]
+fn bad_shortcut_changes_are_refused() {
+    let data_dir = scratch_dir("shortcuts-refused");
+    let shortcuts = |args: &[&str]| fluorite(&data_dir, &[&["shortcuts"], args].concat(), "").status;
+    assert_eq!(shortcuts(&["add", "Attack", "1d20"]), 0);
+    assert_eq!(shortcuts(&["add", "Damage", "1d8"]), 0);
+
+    assert_eq!(shortcuts(&["add", "Attack", "1d20 + 1"]), 1);
+    assert_eq!(shortcuts(&["add", " ", "1d20"]), 1);
+    assert_eq!(shortcuts(&["add", "Broken", "(1d20"]), 2);
+    assert_eq!(shortcuts(&["rename", "Attack", "Damage"]), 1);
+    assert_eq!(shortcuts(&["rename", "Nothing", "Something"]), 1);
+    assert_eq!(shortcuts(&["delete", "Nothing"]), 1);
+    assert_eq!(saved_shortcuts(&data_dir), [pair("Damage", "1d8"), pair("Attack", "1d20")]);
+}
+
+#[test]
+fn shortcuts_are_listed_in_each_format() {
+    let data_dir = scratch_dir("shortcuts-list");
+    fluorite(&data_dir, &["shortcuts", "add", "Attack", "1d20"], "");
+    fluorite(&data_dir, &["shortcuts", "add", "Fireball, upcast", "9d6"], "");
+    let list = |format: &str| fluorite(&data_dir, &["--format", format, "shortcuts", "list"], "").stdout;
+
+    assert_eq!(list("plain"), "Fireball, upcast  9d6\nAttack            1d20\n");
+    assert_eq!(list("csv"), "name,roll\n\"Fireball, upcast\",9d6\nAttack,1d20\n");
+    assert_eq!(list("ndjson"), "{\"name\":\"Fireball, upcast\",\"roll\":\"9d6\"}\n{\"name\":\"Attack\",\"roll\":\"1d20\"}\n");
+    assert_eq!(serde_json::from_str::<Value>(&list("json")).unwrap(), json!([{"name": "Fireball, upcast", "roll": "9d6"}, {"name": "Attack", "roll": "1d20"}]));
+}
+
+#[test]
+fn shortcuts_are_rolled_by_name() {
+    let data_dir = scratch_dir("shortcuts-run");
+    fluorite(&data_dir, &["shortcuts", "add", "Steady", "2d1 + 3"], "");
+
+    assert_eq!(fluorite(&data_dir, &["--no-log", "run", "Steady"], "").stdout, "5\n");
+    assert_eq!(lines(&fluorite(&data_dir, &["--no-log", "repl"], "Steady\n").stdout)[1..], ["5"]);
+    let run = fluorite(&data_dir, &["--no-log", "run", "Unsteady"], "");
+    assert_eq!(run.status, 1);
+    assert_eq!(run.stderr, "fluorite: there's no shortcut named \"Unsteady\"\n");
+}
+
+#[test]
+fn every_seeded_repl_roll_can_be_replayed_from_the_log() {
+    let data_dir = scratch_dir("repl-replay");
+    fluorite(&data_dir, &["repl"], ":seed 42\n10d20\n4d6s + 2\n1d100\n");
+    let entries = DataDir::new(data_dir.clone()).session_log().entries().unwrap();
+
+    assert_eq!(entries.len(), 3);
+    for entry in entries {
+        let source = entry.rng.unwrap();
+        assert_eq!(source, RngSource::Seeded { seed: source.seed(), sequence: 42 });
+        let replayed = parse(&entry.input).unwrap().roll_with_rng(&EvaluationContext::new(), &mut StdRng::seed_from_u64(source.seed())).unwrap();
+        assert_eq!(replayed.processed_string, entry.result.unwrap().processed_string);
+    }
+}
+
+#[test]
+fn log_and_export_pick_out_the_same_rolls() {
+    let data_dir = scratch_dir("log-filters");
+    fluorite(&data_dir, &["1d1"], "");
+    fluorite(&data_dir, &["--profile", "Aria", "2d1"], "");
+    fluorite(&data_dir, &["3d1"], "");
+    let logged = |filter: &[&str]| -> Vec<String> {
+        let run = fluorite(&data_dir, &[&["--format", "ndjson", "log"], filter].concat(), "");
+        run.stdout.lines().map(|line| serde_json::from_str::<Value>(line).unwrap()["input"].as_str().unwrap().to_string()).collect()
+    };
+    let exported = |filter: &[&str]| -> Vec<String> {
+        let run = fluorite(&data_dir, &[&["export", "--to", "csv"], filter].concat(), "");
+        run.stdout.lines().skip(1).map(|line| line.split(',').nth(2).unwrap().to_string()).collect()
+    };
+
+    for (filter, expected) in [(&[][..], &["1d1", "2d1", "3d1"][..]), (&["--last", "2"], &["2d1", "3d1"]), (&["--profile", "Aria"], &["2d1"]), (&["--since", "2999-01-01"], &[]), (&["--until", "2999-01-01", "--last", "1"], &["3d1"])].iter() {
+        assert_eq!(logged(filter), *expected);
+        assert_eq!(exported(filter), *expected);
+    }
+}
+
+#[test]
+fn log_and_export_write_the_same_csv() {
+    let data_dir = scratch_dir("log-csv");
+    fluorite(&data_dir, &["--profile", "Aria", "2d1"], "");
+    // Outside UTC, so the times show which zone each was written in.
+    let csv = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_fluorite")).env("TZ", "IST-5:30").arg("--data-dir").arg(&data_dir).args(args).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let logged = csv(&["--format", "csv", "log"]);
+
+    assert!(lines(&logged)[1].contains("+05:30,Aria,2d1,"));
+    assert_eq!(logged, csv(&["export", "--to", "csv"]));
+}
+
+#[test]
+fn custom_dice_come_from_the_data_directory_unless_a_file_is_given() {
+    let data_dir = scratch_dir("dice");
+    create_dir_all(&data_dir).unwrap();
+    write(data_dir.join("dice.json"), r#"{"dice": [{"name": "ones", "faces": [1]}]}"#).unwrap();
+    let other = data_dir.join("other.json");
+    write(&other, r#"{"dice": [{"name": "twos", "faces": [2]}]}"#).unwrap();
+
+    assert_eq!(fluorite(&data_dir, &["--no-log", "3dones"], "").stdout, "3\n");
+    let overridden = fluorite(&data_dir, &["--no-log", "--dice-file", other.to_str().unwrap()], "3dtwos\n3dones\n");
+    assert_eq!(overridden.stdout, "6\n");
+    assert_eq!(overridden.status, 3);
+
+    // A broken dice.json is reported, and the built-in dice still roll.
+    write(data_dir.join("dice.json"), "{").unwrap();
+    let run = fluorite(&data_dir, &["--no-log", "1dboost + 1dboost"], "");
+    assert_eq!(run.status, 0);
+    assert!(run.stderr.starts_with("fluorite: Failed to parse dice definitions in ") && run.stderr.ends_with(". Rolling with only the built-in dice.\n"));
+}
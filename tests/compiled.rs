@@ -0,0 +1,68 @@
+use fluorite::limits::ResourceLimits;
+use fluorite::parse::{parse, CompiledRoll, EvaluationContext};
+use fluorite::systems::GameSystem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn compiled_rolls_can_be_shared_between_threads() {
+    assert_send_sync::<CompiledRoll>();
+}
+
+#[test]
+fn seeded_rolls_repeat() {
+    let roll = parse("4d6s + 2d10 * 3").unwrap();
+    let context = EvaluationContext::new();
+    let first = roll.roll_with_rng(&context, &mut StdRng::seed_from_u64(7)).unwrap();
+    let second = roll.roll_with_rng(&context, &mut StdRng::seed_from_u64(7)).unwrap();
+
+    assert_eq!(first.value, second.value);
+    assert_eq!(first.processed_string, second.processed_string);
+}
+
+#[test]
+fn seeded_system_rolls_repeat() {
+    let limits = ResourceLimits::new();
+    let first = GameSystem::Chronicles.roll_with_rng(8, &limits, &mut StdRng::seed_from_u64(3)).unwrap();
+    let second = GameSystem::Chronicles.roll_with_rng(8, &limits, &mut StdRng::seed_from_u64(3)).unwrap();
+
+    assert_eq!(first.processed_string, second.processed_string);
+}
+
+#[test]
+fn display_is_canonical() {
+    assert_eq!(parse("2d6+3").unwrap().to_string(), "2d6 + 3");
+    assert_eq!(parse("((1+2))*3").unwrap().to_string(), "(1 + 2) * 3");
+    assert_eq!(parse("1-(2-3)").unwrap().to_string(), "1 - (2 - 3)");
+    assert_eq!(parse("+2.50d(6)").unwrap().to_string(), "2.5d6");
+    assert_eq!(parse("(1+1)d6s").unwrap().to_string(), "(1 + 1)d6s");
+    assert_eq!(parse("-(2d6)").unwrap().to_string(), "-(2d6)");
+    assert_eq!(parse("crit(1d8+2)").unwrap().to_string(), "crit(1d8 + 2)");
+    assert_eq!(parse("coc(50)p2").unwrap().to_string(), "coc(50)p2");
+}
+
+#[test]
+fn canonical_form_parses_to_the_same_expression() {
+    for input in &["2d6+3", "(1+2)*3", "1-(2-3)", "2d6d4", "(2d6)c6", "-(-3)", "1 - -2", "2d-6", "4dability + 2ddifficulty", "coc(40+5)b", "crit(2d6)*2 % 5"] {
+        let roll: CompiledRoll = input.parse().unwrap();
+        assert_eq!(roll.to_string().parse::<CompiledRoll>().unwrap(), roll, "{} became {}", input, roll);
+    }
+}
+
+#[test]
+fn serde_round_trips_through_the_canonical_form() {
+    let roll = parse("3d6 + 1d4 * 2").unwrap();
+    let json = serde_json::to_string(&roll).unwrap();
+
+    assert_eq!(json, "\"3d6 + 1d4 * 2\"");
+    assert_eq!(serde_json::from_str::<CompiledRoll>(&json).unwrap(), roll);
+    assert!(serde_json::from_str::<CompiledRoll>("\"3d\"").is_err());
+}
+
+#[test]
+fn rolling_a_compiled_roll_matches_parse_input() {
+    let roll = parse("10 * 3 - 4 / 2").unwrap();
+    assert_eq!(roll.roll(&EvaluationContext::new()).unwrap().processed_string, "10 * 3 - 4 / 2");
+}
@@ -0,0 +1,124 @@
+use fluorite::parse::{parse, AttackDie, CritMode, EvaluationContext, NaturalResult, RollInformation};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal::Decimal;
+
+fn roll(input: &str, context: &EvaluationContext, seed: u64) -> RollInformation {
+    parse(input).unwrap().roll_with_rng(context, &mut StdRng::seed_from_u64(seed)).unwrap()
+}
+
+fn sum(dice: &[Decimal]) -> Decimal {
+    dice.iter().sum()
+}
+
+#[test]
+fn doubling_rolls_twice_as_many_dice() {
+    let context = EvaluationContext::new();
+    for seed in 0..20 {
+        let information = roll("crit(2d6)", &context, seed);
+        let pool = information.pool.clone().unwrap();
+
+        assert_eq!(pool.len(), 4);
+        assert!(pool.iter().all(|die| *die >= Decimal::from(1) && *die <= Decimal::from(6)));
+        assert_eq!(information.value.number().unwrap(), sum(&pool));
+        assert!(information.processed_string.starts_with("crit([") && information.processed_string.ends_with("])"));
+    }
+}
+
+#[test]
+fn doubling_leaves_modifiers_alone() {
+    let context = EvaluationContext::new();
+    let information = roll("crit(1d8 + 3)", &context, 5);
+    let dice = information.processed_string.strip_prefix("crit([").unwrap().strip_suffix("] + 3)").unwrap();
+    let faces: Vec<Decimal> = dice.split(", ").map(|face| face.parse().unwrap()).collect();
+
+    assert_eq!(faces.len(), 2);
+    assert_eq!(information.value.number().unwrap(), sum(&faces) + Decimal::from(3));
+}
+
+#[test]
+fn maximizing_adds_the_highest_result_of_the_same_dice() {
+    let context = EvaluationContext {
+        crit_mode: CritMode::Maximize,
+        ..EvaluationContext::new()
+    };
+    for seed in 0..20 {
+        let information = roll("crit(2d6)", &context, seed);
+        let pool = information.pool.clone().unwrap();
+
+        assert_eq!(pool.len(), 4);
+        assert_eq!(&pool[2..], &[Decimal::from(6), Decimal::from(6)]);
+        assert_eq!(information.value.number().unwrap(), sum(&pool[..2]) + Decimal::from(12));
+        assert!(information.processed_string.ends_with(" + [6, 6])"));
+    }
+}
+
+#[test]
+fn a_critical_context_treats_the_whole_roll_as_a_crit() {
+    let context = EvaluationContext {
+        critical: true,
+        ..EvaluationContext::new()
+    };
+    let information = roll("3d4", &context, 1);
+
+    assert_eq!(information.pool.unwrap().len(), 6);
+    // The same roll outside a crit.
+    assert_eq!(roll("3d4", &EvaluationContext::new(), 1).pool.unwrap().len(), 3);
+}
+
+#[test]
+fn a_natural_20_is_a_critical_hit_and_a_natural_1_a_critical_miss() {
+    let die = |face| AttackDie {
+        face: Decimal::from(face),
+        sides: Decimal::from(20),
+    };
+
+    assert_eq!(die(20).natural(), Some(NaturalResult::CriticalHit));
+    assert_eq!(die(1).natural(), Some(NaturalResult::CriticalMiss));
+    assert_eq!(die(10).natural(), None);
+}
+
+#[test]
+fn the_attack_die_is_found_in_rolls() {
+    let context = EvaluationContext::new();
+    let (mut hits, mut misses) = (0, 0);
+    for seed in 0..200 {
+        let information = roll("1d20 + 5", &context, seed);
+        let attack_die = information.attack_die.unwrap();
+        assert_eq!(information.value.number().unwrap(), attack_die.face + Decimal::from(5));
+        match information.natural() {
+            Some(NaturalResult::CriticalHit) => {
+                assert_eq!(attack_die.face, Decimal::from(20));
+                hits += 1;
+            }
+            Some(NaturalResult::CriticalMiss) => {
+                assert_eq!(attack_die.face, Decimal::from(1));
+                misses += 1;
+            }
+            None => assert!(attack_die.face > Decimal::from(1) && attack_die.face < Decimal::from(20)),
+        }
+    }
+
+    assert!(hits > 0 && misses > 0);
+}
+
+#[test]
+fn only_a_single_die_of_the_attack_size_is_an_attack_die() {
+    let context = EvaluationContext::new();
+
+    assert!(roll("2d20", &context, 1).attack_die.is_none());
+    assert!(roll("1d12", &context, 1).attack_die.is_none());
+    assert!(roll("crit(1d20)", &context, 1).attack_die.is_none());
+
+    let no_attack_die = EvaluationContext {
+        attack_die_sides: None,
+        ..EvaluationContext::new()
+    };
+    assert!(roll("1d20", &no_attack_die, 1).attack_die.is_none());
+
+    let d10_attacks = EvaluationContext {
+        attack_die_sides: Some(10),
+        ..EvaluationContext::new()
+    };
+    assert!(roll("1d10", &d10_attacks, 1).attack_die.is_some());
+}
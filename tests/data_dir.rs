@@ -0,0 +1,32 @@
+use fluorite::storage::DataDir;
+use std::env::{remove_var, set_var};
+use std::path::Path;
+
+// Everything touching environment variables lives in this one test, so nothing else races with it.
+#[cfg(not(windows))]
+#[test]
+fn per_user_directories_follow_xdg() {
+    set_var("HOME", "/home/player");
+    remove_var("XDG_CONFIG_HOME");
+    remove_var("XDG_DATA_HOME");
+    let data_dir = DataDir::per_user().unwrap();
+    assert_eq!(data_dir.config_path(), Path::new("/home/player/.config/fluorite"));
+    assert_eq!(data_dir.data_path(), Path::new("/home/player/.local/share/fluorite"));
+
+    set_var("XDG_CONFIG_HOME", "/xdg/config");
+    set_var("XDG_DATA_HOME", "/xdg/data");
+    let data_dir = DataDir::per_user().unwrap();
+    assert_eq!(data_dir.config().path(), Path::new("/xdg/config/fluorite/config.json"));
+    assert_eq!(data_dir.shortcuts().path(), Path::new("/xdg/data/fluorite/shortcuts.json"));
+
+    // Relative paths are invalid under the specification, so they're ignored.
+    set_var("XDG_DATA_HOME", "relative/data");
+    assert_eq!(DataDir::per_user().unwrap().data_path(), Path::new("/home/player/.local/share/fluorite"));
+}
+
+#[test]
+fn an_explicit_directory_holds_everything() {
+    let data_dir = DataDir::locate(Some("/games/fluorite".into())).unwrap();
+    assert_eq!(data_dir.config().path(), Path::new("/games/fluorite/config.json"));
+    assert_eq!(data_dir.history().path(), Path::new("/games/fluorite/history.json"));
+}
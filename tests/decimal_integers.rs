@@ -0,0 +1,57 @@
+use fluorite::parse::parse_input;
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+
+fn pool_of(input: &str) -> Vec<Decimal> {
+    let information = parse_input(input).unwrap();
+    information.pool.unwrap()
+}
+
+fn assert_valid_pool(pool: &[Decimal], number: u64, sides: u64) {
+    assert_eq!(pool.len() as u64, number);
+    for face in pool {
+        assert!(*face >= Decimal::from(1) && *face <= Decimal::from(sides), "Face {} is outside 1..={}", face, sides);
+    }
+}
+
+#[test]
+fn two_point_zero_dice_rolls_two_dice() {
+    assert_valid_pool(&pool_of("2.0d6"), 2, 6);
+}
+
+#[test]
+fn six_point_zero_sides_is_a_d6() {
+    assert_valid_pool(&pool_of("1d6.0"), 1, 6);
+    assert_valid_pool(&pool_of("1d6.00"), 1, 6);
+}
+
+#[test]
+fn fractional_dice_are_still_rejected() {
+    assert!(parse_input("2.5d6").is_err());
+    assert!(parse_input("2d6.5").is_err());
+}
+
+#[test]
+fn zero_sided_dice_are_rejected() {
+    assert!(parse_input("1d0").is_err());
+    assert!(parse_input("1d0.0").is_err());
+}
+
+proptest! {
+    #[test]
+    fn decimal_written_integers_roll_like_integers(number in 1u64..40, sides in 1u64..200, number_zeros in 0usize..4, sides_zeros in 0usize..4) {
+        let number_written = if number_zeros == 0 { number.to_string() } else { format!("{}.{}", number, "0".repeat(number_zeros)) };
+        let sides_written = if sides_zeros == 0 { sides.to_string() } else { format!("{}.{}", sides, "0".repeat(sides_zeros)) };
+
+        assert_valid_pool(&pool_of(&format!("{}d{}", number, sides)), number, sides);
+        assert_valid_pool(&pool_of(&format!("{}d{}", number_written, sides_written)), number, sides);
+    }
+
+    #[test]
+    fn decimal_written_integers_are_accepted_or_rejected_alike(number in 0i64..40, sides in -5i64..5) {
+        let integer_result = parse_input(&format!("{}d{}", number, sides));
+        let decimal_result = parse_input(&format!("{}.0d{}.0", number, sides));
+
+        prop_assert_eq!(integer_result.is_ok(), decimal_result.is_ok());
+    }
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, TimeZone, Utc};
+use fluorite::export::{export, summarize_error, ExportFormat};
+use fluorite::parse::{parse, EvaluationContext};
+use fluorite::precision::DisplayPolicy;
+use fluorite::session::{LogEntry, RngSource};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::path::Path;
+
+fn june(day: u32) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&NaiveDate::from_ymd_opt(2021, 6, day).unwrap().and_hms_opt(18, 30, 0).unwrap())
+}
+
+fn rolled(input: &str, seed: u64) -> LogEntry {
+    let roll = parse(input).unwrap();
+    let result = roll.roll_with_rng(&EvaluationContext::new(), &mut StdRng::seed_from_u64(seed)).map_err(|e| e.to_string());
+    LogEntry {
+        time: june(1),
+        ..LogEntry::new(roll.to_string(), result, Some(RngSource::Entropy { seed }))
+    }
+}
+
+fn failed(input: &str) -> LogEntry {
+    LogEntry {
+        time: june(2),
+        ..LogEntry::new(input, Err(parse(input).unwrap_err().to_string()), None)
+    }
+}
+
+#[test]
+fn markdown_is_a_table_of_times_inputs_traces_and_results() {
+    let entry = rolled("2d6 + 3", 7);
+    let results = entry.result.as_ref().unwrap().clone();
+    let document = export(&[entry, failed("2d6 +")], ExportFormat::Markdown, &DisplayPolicy::new(), &Utc);
+    let lines: Vec<&str> = document.lines().collect();
+
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "| Time | Input | Rolled | Result |");
+    assert_eq!(lines[2], format!("| 2021-06-01 18:30:00 | `2d6 + 3` | `{}` | **{}** |", results.processed_string, results.value));
+    // A syntax error is summed up in the line which says what was expected, so the row stays one line.
+    assert!(lines[3].starts_with("| 2021-06-02 18:30:00 | `2d6 +` |  | Error: "));
+    assert_eq!(lines[3].matches(" | ").count(), 3);
+}
+
+#[test]
+fn markdown_escapes_anything_which_would_break_the_table() {
+    let entry = LogEntry {
+        time: june(3),
+        ..LogEntry::new("a | b", Err(String::from("no *such* die | d7")), None)
+    };
+    let document = export(&[entry], ExportFormat::Markdown, &DisplayPolicy::new(), &Utc);
+
+    assert_eq!(document.lines().nth(2).unwrap(), "| 2021-06-03 18:30:00 | `a \\| b` |  | Error: no \\*such\\* die \\| d7 |");
+    assert_eq!(export(&[], ExportFormat::Markdown, &DisplayPolicy::new(), &Utc), "No rolls.\n");
+}
+
+#[test]
+fn html_is_a_standalone_page_with_everything_escaped() {
+    let error = failed("2d6 +");
+    let message = error.result.clone().unwrap_err();
+    let entries = [rolled("2d6 + 3", 7), error];
+    let document = export(&entries, ExportFormat::Html, &DisplayPolicy::new(), &Utc);
+
+    assert!(document.starts_with("<!DOCTYPE html>"));
+    assert!(document.contains("<style>"));
+    assert!(document.trim_end().ends_with("</html>"));
+    assert!(document.contains("2 rolls, from 2021-06-01 18:30:00 to 2021-06-02 18:30:00."));
+    assert!(document.contains("<time datetime=\"2021-06-01T18:30:00Z\">2021-06-01 18:30:00</time>"));
+    assert!(document.contains("<code>2d6 + 3</code>"));
+    // The whole syntax error is kept, so its pointer still lines up with the input.
+    assert!(document.contains("<tr class=\"error\">"));
+    assert!(document.contains(&format!("<pre>{}</pre>", message.trim_end().replace('>', "&gt;").replace('<', "&lt;"))));
+
+    let entry = LogEntry {
+        time: june(3),
+        ..LogEntry::new("<script>", Err(String::from("\"&\"")), None)
+    };
+    let document = export(&[entry], ExportFormat::Html, &DisplayPolicy::new(), &Utc);
+    assert!(document.contains("<code>&lt;script&gt;</code>"));
+    assert!(document.contains("<pre>&quot;&amp;&quot;</pre>"));
+    assert!(!document.contains("<script>"));
+}
+
+#[test]
+fn times_are_shown_in_the_zone_given() {
+    let entries = [rolled("1d20", 1)];
+    let zone = FixedOffset::east_opt(10 * 3600).unwrap();
+
+    assert!(export(&entries, ExportFormat::Markdown, &DisplayPolicy::new(), &zone).contains("| 2021-06-02 04:30:00 |"));
+    assert!(export(&entries, ExportFormat::Csv, &DisplayPolicy::new(), &zone).contains("2021-06-02T04:30:00.000+10:00,"));
+}
+
+#[test]
+fn csv_has_a_row_per_roll_with_awkward_fields_quoted() {
+    let mut entry = rolled("1d6", 3);
+    entry.time = june(1) + Duration::milliseconds(250);
+    entry.profile = Some(String::from("Tuesday, online"));
+    entry.shortcut = Some(String::from("Say \"hi\""));
+    let results = entry.result.as_ref().unwrap().clone();
+    let document = export(&[entry, failed("1d")], ExportFormat::Csv, &DisplayPolicy::new(), &Utc);
+    let lines: Vec<&str> = document.lines().collect();
+
+    assert_eq!(lines[0], "time,profile,input,trace,value,display,outcome,error,rng_source,rng_seed,shortcut");
+    assert_eq!(
+        lines[1],
+        format!("2021-06-01T18:30:00.250Z,\"Tuesday, online\",1d6,{},{},{},,,entropy,3,\"Say \"\"hi\"\"\"", results.processed_string, results.value, results.value)
+    );
+    // Syntax errors span several lines, so the error field is quoted and the record goes on past the end of the line.
+    assert!(lines[2].starts_with("2021-06-02T18:30:00.000Z,,1d,,,,,\""));
+    assert_eq!(export(&[], ExportFormat::Csv, &DisplayPolicy::new(), &Utc).lines().count(), 1);
+}
+
+#[test]
+fn formats_are_known_by_name_and_extension() {
+    assert_eq!("markdown".parse(), Ok(ExportFormat::Markdown));
+    assert_eq!("HTML".parse(), Ok(ExportFormat::Html));
+    assert!("pdf".parse::<ExportFormat>().is_err());
+
+    for format in [ExportFormat::Markdown, ExportFormat::Html, ExportFormat::Csv].iter() {
+        let path = format!("rolls.{}", format.extension());
+        assert_eq!(ExportFormat::from_path(Path::new(&path)), Some(*format));
+    }
+    assert_eq!(ExportFormat::from_path(Path::new("rolls.htm")), Some(ExportFormat::Html));
+    assert_eq!(ExportFormat::from_path(Path::new("rolls")), None);
+}
+
+#[test]
+fn errors_are_summed_up_in_their_last_line() {
+    assert_eq!(summarize_error("Division by zero."), "Division by zero.");
+    assert_eq!(summarize_error(" --> 1:5\n  |\n1 | 2d6 +\n  |     ^---\n  |\n  = expected die\n"), "expected die");
+}
@@ -0,0 +1,146 @@
+use fluorite::error::RollError;
+use fluorite::limits::{Limit, ResourceLimits};
+use fluorite::parse::{parse, parse_input_with_context, parse_with_limits, EvaluationContext, RollInformation};
+use fluorite::systems::GameSystem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+fn context(limits: ResourceLimits) -> EvaluationContext {
+    EvaluationContext {
+        limits,
+        summarize_above: None,
+        ..EvaluationContext::new()
+    }
+}
+
+fn roll(input: &str, context: &EvaluationContext) -> Result<RollInformation, RollError> {
+    parse(input).unwrap().roll_with_rng(context, &mut StdRng::seed_from_u64(1))
+}
+
+fn exceeded(limit: Limit, maximum: u64) -> RollError {
+    RollError::LimitExceeded { limit, maximum }
+}
+
+#[test]
+fn a_group_may_hold_up_to_the_dice_per_group() {
+    let limits = ResourceLimits {
+        max_dice_per_group: 10,
+        ..ResourceLimits::new()
+    };
+
+    assert_eq!(roll("10d6", &context(limits)).unwrap().pool.unwrap().len(), 10);
+    assert_eq!(roll("11d6", &context(limits)).unwrap_err(), exceeded(Limit::DicePerGroup, 10));
+    assert_eq!(roll("2d6 + (5 + 6)d6", &context(limits)).unwrap_err(), exceeded(Limit::DicePerGroup, 10));
+}
+
+#[test]
+fn every_group_counts_towards_the_total_dice() {
+    let limits = ResourceLimits {
+        max_total_dice: 10,
+        ..ResourceLimits::new()
+    };
+
+    assert!(roll("5d6 + 5d6", &context(limits)).is_ok());
+    assert_eq!(roll("5d6 + 6d6", &context(limits)).unwrap_err(), exceeded(Limit::TotalDice, 10));
+    assert_eq!(roll("crit(6d6)", &context(limits)).unwrap_err(), exceeded(Limit::TotalDice, 10));
+}
+
+#[test]
+fn summarized_groups_count_only_towards_the_total_dice() {
+    let limits = ResourceLimits {
+        max_dice_per_group: 10,
+        max_total_dice: 100,
+        ..ResourceLimits::new()
+    };
+    let summarizing = EvaluationContext {
+        summarize_above: Some(10),
+        ..context(limits)
+    };
+
+    assert!(roll("100d6", &summarizing).is_ok());
+    assert_eq!(roll("101d6", &summarizing).unwrap_err(), exceeded(Limit::TotalDice, 100));
+}
+
+#[test]
+fn huge_groups_fail_without_rolling() {
+    assert_eq!(roll("999999999999d6", &EvaluationContext::new()).unwrap_err(), exceeded(Limit::TotalDice, 100_000));
+    assert_eq!(roll("999999999999d6", &context(ResourceLimits::new())).unwrap_err(), exceeded(Limit::DicePerGroup, 10_000));
+}
+
+#[test]
+fn nesting_is_checked_before_parsing() {
+    let limits = ResourceLimits {
+        max_nesting_depth: 3,
+        ..ResourceLimits::new()
+    };
+
+    assert!(parse_with_limits("((1 + (2)) * (3))", &limits).is_ok());
+    assert!(parse_with_limits("(((1)))", &limits).is_ok());
+    assert_eq!(parse_with_limits("((((1))))", &limits).unwrap_err(), exceeded(Limit::NestingDepth, 3));
+    // Too deep to parse even when unbalanced.
+    assert_eq!(parse_with_limits("((((1", &limits).unwrap_err(), exceeded(Limit::NestingDepth, 3));
+
+    let default_depth = ResourceLimits::new().max_nesting_depth as usize;
+    let nested = |depth| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert!(parse(&nested(default_depth)).is_ok());
+    assert_eq!(parse(&nested(default_depth + 1)).unwrap_err(), exceeded(Limit::NestingDepth, 32));
+}
+
+#[test]
+fn input_length_is_counted_after_cleaning() {
+    let limits = ResourceLimits {
+        max_input_length: 9,
+        ..ResourceLimits::new()
+    };
+
+    assert!(parse_with_limits("1d6 + 1d6", &limits).is_ok());
+    assert_eq!(parse_with_limits("1d6 + 1d6 ", &limits).unwrap_err(), exceeded(Limit::InputLength, 9));
+    assert!(parse_with_limits("1d6 + 1d6!?", &limits).is_ok());
+    assert_eq!(parse(&"1".repeat(1_001)).unwrap_err(), exceeded(Limit::InputLength, 1_000));
+}
+
+#[test]
+fn rolls_use_the_limits_in_their_context() {
+    let limits = ResourceLimits {
+        max_input_length: 3,
+        ..ResourceLimits::new()
+    };
+
+    assert!(parse_input_with_context("1d6", &context(limits)).is_ok());
+    assert_eq!(parse_input_with_context("1d20", &context(limits)).unwrap_err(), exceeded(Limit::InputLength, 3));
+}
+
+#[test]
+fn ten_again_chains_stop_at_the_limit() {
+    let limits = ResourceLimits {
+        max_explosion_chain: 0,
+        ..ResourceLimits::new()
+    };
+    let (mut chained, mut unchained) = (0, 0);
+    for seed in 0..100 {
+        let unlimited = GameSystem::Chronicles.roll_with_rng(5, &ResourceLimits::new(), &mut StdRng::seed_from_u64(seed)).unwrap();
+        match GameSystem::Chronicles.roll_with_rng(5, &limits, &mut StdRng::seed_from_u64(seed)) {
+            Ok(information) => {
+                assert!(!unlimited.processed_string.contains("10-again"));
+                assert_eq!(information.processed_string, unlimited.processed_string);
+                unchained += 1;
+            }
+            Err(e) => {
+                assert!(unlimited.processed_string.contains("10-again"));
+                assert_eq!(e, exceeded(Limit::ExplosionChain, 0));
+                chained += 1;
+            }
+        }
+    }
+
+    assert!(chained > 0 && unchained > 0);
+}
+
+#[test]
+fn limit_errors_say_which_limit_was_hit() {
+    let error = exceeded(Limit::NestingDepth, 3);
+
+    assert_eq!(error.kind(), "limit");
+    assert_eq!(error.to_string(), "Limit exceeded: at most 3 levels of nested parentheses allowed.");
+    assert_eq!(exceeded(Limit::DicePerGroup, 10).to_string(), "Limit exceeded: at most 10 dice per group allowed.");
+}
@@ -0,0 +1,91 @@
+use fluorite::error::StorageError;
+use fluorite::parse::{ArithmeticMode, CritMode, RollValue};
+use fluorite::storage::{DataDir, RollShortcut};
+use serde_json::Value;
+use std::env::temp_dir;
+use std::fs::{copy, create_dir_all, read_to_string, remove_dir_all, write};
+use std::path::Path;
+
+/// A fresh data directory holding copies of the files in `tests/fixtures/<fixtures>`.
+fn data_dir_from(fixtures: &str) -> DataDir {
+    let path = temp_dir().join(format!("fluorite-migration-test-{}-{}", fixtures, std::process::id()));
+    let _ = remove_dir_all(&path);
+    create_dir_all(&path).unwrap();
+    for file in &["config.json", "history.json", "shortcuts.json"] {
+        copy(Path::new("tests/fixtures").join(fixtures).join(file), path.join(file)).unwrap();
+    }
+    DataDir::new(path)
+}
+
+fn saved_version(path: &Path) -> Option<u64> {
+    let document: Value = serde_json::from_str(&read_to_string(path).unwrap()).unwrap();
+    document.get("version").and_then(Value::as_u64)
+}
+
+#[test]
+fn original_files_load_and_upgrade() {
+    let data_dir = data_dir_from("v0-original");
+
+    let config = data_dir.config().load().unwrap();
+    assert_eq!(config.max_history_entries, 100);
+    assert_eq!(config.crit_mode, CritMode::Double);
+
+    let history = data_dir.history().load().unwrap();
+    assert_eq!(history.len(), 3);
+    let first = history[0].1.as_ref().unwrap();
+    assert_eq!(first.value, RollValue::Number(11.into()));
+    assert_eq!(first.processed_string, "[4, 4] + 3");
+    assert_eq!(history[2].1.as_ref().unwrap_err(), "Failed to parse input.");
+
+    let shortcuts = data_dir.shortcuts().load().unwrap();
+    assert_eq!(
+        shortcuts,
+        vec![RollShortcut {
+            name: String::from("Damage"),
+            roll: String::from("2d6+3"),
+        }]
+    );
+
+    // Each file is rewritten in the current format, with the original kept beside it.
+    for (path, original) in &[(data_dir.config().path().to_owned(), data_dir.config().original_path(0)), (data_dir.shortcuts().path().to_owned(), data_dir.shortcuts().original_path(0))] {
+        assert_eq!(saved_version(path), Some(1));
+        assert_eq!(read_to_string(original).unwrap(), read_to_string(Path::new("tests/fixtures/v0-original").join(path.file_name().unwrap())).unwrap());
+    }
+    assert_eq!(data_dir.shortcuts().load().unwrap(), shortcuts);
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
+
+#[test]
+fn unversioned_files_with_every_field_load_and_upgrade() {
+    let data_dir = data_dir_from("v0");
+
+    let config = data_dir.config().load().unwrap();
+    assert_eq!(config.max_history_entries, 50);
+    assert!(!config.save_shortcuts);
+    assert_eq!(config.crit_mode, CritMode::Maximize);
+    assert_eq!(config.arithmetic, ArithmeticMode::Rational);
+
+    let history = data_dir.history().load().unwrap();
+    assert_eq!(history.len(), 6);
+    assert!(history[0].1.as_ref().unwrap().attack_die.is_some());
+    assert_eq!(history[1].1.as_ref().unwrap().pool.as_ref().unwrap().len(), 4);
+    assert!(matches!(history[2].1.as_ref().unwrap().value, RollValue::Rational(_)));
+    assert!(matches!(history[3].1.as_ref().unwrap().value, RollValue::Symbols(_)));
+    assert_eq!(history[4].1.as_ref().unwrap().outcome.as_deref(), Some("Bad outcome"));
+
+    assert_eq!(data_dir.shortcuts().load().unwrap().len(), 2);
+    assert_eq!(saved_version(data_dir.history().path()), Some(1));
+    assert!(data_dir.history().original_path(0).exists());
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
+
+#[test]
+fn files_from_newer_versions_are_left_alone() {
+    let data_dir = data_dir_from("v0");
+    let newer = r#"{"version": 99, "data": [{"name": "From the future", "roll": "1d6", "colour": "blue"}]}"#;
+    write(data_dir.shortcuts().path(), newer).unwrap();
+
+    assert!(matches!(data_dir.shortcuts().load(), Err(StorageError::NewerVersion { version: 99, supported: 1, .. })));
+    assert_eq!(read_to_string(data_dir.shortcuts().path()).unwrap(), newer);
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
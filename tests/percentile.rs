@@ -0,0 +1,139 @@
+use fluorite::error::RollError;
+use fluorite::limits::ResourceLimits;
+use fluorite::parse::{parse, parse_input, EvaluationContext, RollInformation};
+use fluorite::systems::GameSystem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal::prelude::*;
+use std::collections::BTreeSet;
+
+const SEEDS: u64 = 1_000;
+
+fn roll(input: &str, seed: u64) -> RollInformation {
+    parse(input).unwrap().roll_with_rng(&EvaluationContext::new(), &mut StdRng::seed_from_u64(seed)).unwrap()
+}
+
+/// The tens dice, units die, and result in a trace such as `coc(50)b: tens [30, 70] units [4] -> 34`.
+fn dice(information: &RollInformation) -> (Vec<i64>, i64, i64) {
+    let faces = |list: &str| -> Vec<i64> { list.trim_start_matches('[').trim_end_matches(']').split(", ").map(|face| face.parse().unwrap()).collect() };
+    let (dice, result) = information.processed_string.split("tens ").nth(1).unwrap().split_once(" -> ").unwrap();
+    let (tens, units) = dice.split_once(" units ").unwrap();
+
+    (faces(tens), faces(units)[0], result.parse().unwrap())
+}
+
+/// The d100 results each tens die would give with the units die, counting 00 and 0 as 100.
+fn candidates(tens: &[i64], units: i64) -> Vec<i64> {
+    tens.iter().map(|tens| if *tens == 0 && units == 0 { 100 } else { tens + units }).collect()
+}
+
+fn grade(result: i64, skill: i64) -> &'static str {
+    let fumble = if skill < 50 { 96 } else { 100 };
+    if result == 1 {
+        "Critical success"
+    } else if result >= fumble {
+        "Fumble"
+    } else if result <= skill / 5 {
+        "Extreme success"
+    } else if result <= skill / 2 {
+        "Hard success"
+    } else if result <= skill {
+        "Regular success"
+    } else {
+        "Failure"
+    }
+}
+
+#[test]
+fn tens_and_units_are_rolled_separately() {
+    for seed in 0..SEEDS {
+        let information = roll("coc(50)", seed);
+        let (tens, units, result) = dice(&information);
+
+        assert_eq!(tens.len(), 1);
+        assert!(tens[0] % 10 == 0 && (0..=90).contains(&tens[0]));
+        assert!((0..=9).contains(&units));
+        assert_eq!(result, candidates(&tens, units)[0]);
+        assert!((1..=100).contains(&result));
+        assert_eq!(information.value.number().unwrap(), Decimal::from(result));
+    }
+}
+
+#[test]
+fn bonus_dice_keep_the_lowest_result_and_penalty_dice_the_highest() {
+    for seed in 0..SEEDS {
+        let (tens, units, result) = dice(&roll("coc(50)b2", seed));
+        assert_eq!(tens.len(), 3);
+        assert_eq!(result, *candidates(&tens, units).iter().min().unwrap());
+
+        let (tens, units, result) = dice(&roll("coc(50)p", seed));
+        assert_eq!(tens.len(), 2);
+        assert_eq!(result, *candidates(&tens, units).iter().max().unwrap());
+    }
+}
+
+#[test]
+fn results_are_graded_against_the_skill() {
+    for skill in [10, 40, 49, 50, 75].iter() {
+        let mut seen = BTreeSet::new();
+        for seed in 0..SEEDS {
+            let information = roll(&format!("coc({})", skill), seed);
+            let (_, _, result) = dice(&information);
+            let outcome = information.outcome.unwrap();
+            assert_eq!(outcome, grade(result, *skill), "{} against skill {}", result, skill);
+            seen.insert(outcome);
+        }
+        assert!(seen.contains("Failure") && seen.contains("Regular success") && seen.contains("Hard success"), "skill {} saw {:?}", skill, seen);
+    }
+}
+
+#[test]
+fn every_grade_comes_up() {
+    let mut seen = BTreeSet::new();
+    for seed in 0..SEEDS {
+        seen.insert(roll("coc(40)", seed).outcome.unwrap());
+    }
+
+    for grade in ["Critical success", "Extreme success", "Hard success", "Regular success", "Failure", "Fumble"].iter() {
+        assert!(seen.contains(*grade), "never saw {}", grade);
+    }
+}
+
+#[test]
+fn fumbles_start_lower_for_skills_under_fifty() {
+    for (skill, lowest_fumble) in [(49, 96), (50, 100)].iter() {
+        for seed in 0..SEEDS {
+            let information = roll(&format!("coc({})", skill), seed);
+            let (_, _, result) = dice(&information);
+            assert_eq!(information.outcome.unwrap() == "Fumble", result >= *lowest_fumble, "{} against skill {}", result, skill);
+        }
+    }
+}
+
+#[test]
+fn the_skill_can_be_an_expression() {
+    let information = roll("coc(40 + 5)b", 3);
+
+    assert!(information.processed_string.starts_with("coc(40 + 5)b: tens ["));
+    let (_, _, result) = dice(&information);
+    assert_eq!(information.outcome.unwrap(), grade(result, 45));
+}
+
+#[test]
+fn the_system_preset_rolls_without_extra_dice() {
+    for seed in 0..50 {
+        let information = GameSystem::CallOfCthulhu.roll_with_rng(65, &ResourceLimits::new(), &mut StdRng::seed_from_u64(seed)).unwrap();
+        let (tens, _, result) = dice(&information);
+
+        assert_eq!(tens.len(), 1);
+        assert_eq!(information.outcome.unwrap(), grade(result, 65));
+    }
+}
+
+#[test]
+fn fractional_extra_dice_are_errors() {
+    match parse_input("coc(50)b1.5") {
+        Err(RollError::Evaluation(_)) => (),
+        other => panic!("Expected an evaluation error, got {:?}", other),
+    }
+}
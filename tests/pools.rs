@@ -0,0 +1,103 @@
+use fluorite::parse::{parse, parse_input, EvaluationContext, RollInformation};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal::Decimal;
+
+fn roll(input: &str, seed: u64) -> RollInformation {
+    parse(input).unwrap().roll_with_rng(&EvaluationContext::new(), &mut StdRng::seed_from_u64(seed)).unwrap()
+}
+
+/// The pool from rolling `input`. Pool modifiers roll nothing themselves, so a seed rolls the same dice with or without them.
+fn dice(input: &str, seed: u64) -> Vec<Decimal> {
+    roll(input, seed).pool.unwrap()
+}
+
+#[test]
+fn sorting_puts_the_dice_in_ascending_order() {
+    for seed in 0..20 {
+        let mut expected = dice("8d6", seed);
+        expected.sort();
+        let sorted = roll("8d6s", seed);
+
+        assert_eq!(sorted.pool.clone().unwrap(), expected);
+        assert_eq!(sorted.value.number().unwrap(), expected.iter().sum::<Decimal>());
+        assert!(sorted.processed_string.contains("s -> "));
+    }
+}
+
+#[test]
+fn unique_keeps_the_first_of_each_face() {
+    for seed in 0..20 {
+        let rolled = dice("6d6", seed);
+        let mut expected = Vec::new();
+        for face in rolled.iter() {
+            if !expected.contains(face) {
+                expected.push(*face);
+            }
+        }
+        let unique = roll("6d6u", seed);
+
+        assert_eq!(unique.pool.clone().unwrap(), expected);
+        assert_eq!(unique.value.number().unwrap(), expected.iter().sum::<Decimal>());
+    }
+}
+
+#[test]
+fn counting_gives_how_many_dice_show_a_face() {
+    for seed in 0..20 {
+        let rolled = dice("10d6", seed);
+        for face in 1..=6 {
+            let expected = rolled.iter().filter(|die| **die == Decimal::from(face)).count();
+            let count = roll(&format!("10d6c{}", face), seed);
+
+            assert_eq!(count.value.number().unwrap(), Decimal::from(expected));
+            // A count is a number rather than a pool, so it can't be modified further.
+            assert!(count.pool.is_none());
+        }
+    }
+}
+
+#[test]
+fn matching_keeps_the_largest_set_breaking_ties_by_higher_face() {
+    for seed in 0..20 {
+        let rolled = dice("8d6", seed);
+        let size = |face: &Decimal| rolled.iter().filter(|die| *die == face).count();
+        let largest = rolled.iter().map(size).max().unwrap();
+        let face = rolled.iter().filter(|die| size(die) == largest).max().unwrap();
+        let matching = roll("8d6m", seed);
+
+        assert_eq!(matching.pool.unwrap(), vec![*face; largest]);
+    }
+}
+
+#[test]
+fn modifiers_chain_left_to_right() {
+    for seed in 0..20 {
+        let mut expected = dice("8d6u", seed);
+        expected.sort();
+
+        assert_eq!(dice("8d6us", seed), expected);
+    }
+}
+
+#[test]
+fn pools_are_summed_by_arithmetic() {
+    let information = roll("4d6s + 1", 3);
+    let rolled = dice("4d6", 3);
+
+    assert!(information.pool.is_none());
+    assert_eq!(information.value.number().unwrap(), rolled.iter().sum::<Decimal>() + Decimal::from(1));
+    assert!(parse_input("(4d6 + 1)s").is_err());
+}
+
+#[test]
+fn a_group_of_no_dice_is_an_empty_pool() {
+    assert_eq!(dice("0d6", 1), Vec::<Decimal>::new());
+    assert_eq!(roll("0d6", 1).processed_string, "[]");
+    assert_eq!(roll("0d6", 1).value.number().unwrap(), Decimal::from(0));
+    assert_eq!(dice("0d6s", 1), Vec::<Decimal>::new());
+    assert_eq!(dice("0d6u", 1), Vec::<Decimal>::new());
+    assert_eq!(dice("0d6m", 1), Vec::<Decimal>::new());
+    assert_eq!(roll("0d6c0", 1).value.number().unwrap(), Decimal::from(0));
+    assert_eq!(roll("0d6c6", 1).value.number().unwrap(), Decimal::from(0));
+}
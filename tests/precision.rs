@@ -0,0 +1,98 @@
+use fluorite::parse::{parse_input, RollValue};
+use fluorite::precision::{DisplayPolicy, Precision, RoundingMode};
+use fluorite::rational::Rational;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+fn policy(rounding: RoundingMode, precision: Precision) -> DisplayPolicy {
+    DisplayPolicy { rounding, precision }
+}
+
+fn shown(policy: &DisplayPolicy, number: &str) -> String {
+    policy.format_number(Decimal::from_str(number).unwrap())
+}
+
+/// Checks how each of `cases` is shown when rounded to whole numbers with `rounding`.
+fn assert_rounds(rounding: RoundingMode, cases: &[(&str, &str)]) {
+    let policy = policy(rounding, Precision::Places(0));
+    for (number, expected) in cases {
+        assert_eq!(shown(&policy, number), *expected, "{} rounded with {:?}", number, rounding);
+    }
+}
+
+#[test]
+fn half_up_rounds_halves_away_from_zero() {
+    assert_rounds(RoundingMode::HalfUp, &[("2.5", "3"), ("3.5", "4"), ("-2.5", "-3"), ("2.49", "2"), ("-2.7", "-3")]);
+}
+
+#[test]
+fn bankers_rounds_halves_to_even() {
+    assert_rounds(RoundingMode::Bankers, &[("2.5", "2"), ("3.5", "4"), ("-2.5", "-2"), ("2.51", "3"), ("-3.5", "-4")]);
+}
+
+#[test]
+fn floor_rounds_down() {
+    assert_rounds(RoundingMode::Floor, &[("2.9", "2"), ("2.5", "2"), ("-2.1", "-3"), ("-2.5", "-3"), ("4", "4")]);
+}
+
+#[test]
+fn toward_zero_drops_the_extra_digits() {
+    assert_rounds(RoundingMode::TowardZero, &[("2.9", "2"), ("2.5", "2"), ("-2.9", "-2"), ("-2.5", "-2"), ("-4", "-4")]);
+}
+
+#[test]
+fn results_show_four_decimal_places_by_default() {
+    let policy = DisplayPolicy::new();
+
+    assert_eq!(policy, DisplayPolicy::default());
+    assert_eq!(policy.format_value(&parse_input("10/3").unwrap().value), "3.3333");
+    assert_eq!(policy.format_value(&parse_input("2/3").unwrap().value), "0.6667");
+    assert_eq!(shown(&policy, "1.50000"), "1.5");
+    assert_eq!(shown(&policy, "12"), "12");
+}
+
+#[test]
+fn rounding_applies_at_the_chosen_precision() {
+    assert_eq!(shown(&policy(RoundingMode::HalfUp, Precision::Places(2)), "1.005"), "1.01");
+    assert_eq!(shown(&policy(RoundingMode::Bankers, Precision::Places(2)), "1.005"), "1");
+    assert_eq!(shown(&policy(RoundingMode::Floor, Precision::Places(1)), "-0.25"), "-0.3");
+    assert_eq!(shown(&policy(RoundingMode::TowardZero, Precision::Places(28)), "0.3333333333333333333333333333"), "0.3333333333333333333333333333");
+}
+
+#[test]
+fn rational_precision_shows_the_simplest_fraction() {
+    let policy = policy(RoundingMode::HalfUp, Precision::Rational);
+
+    assert_eq!(policy.format_value(&parse_input("10/3").unwrap().value), "10/3");
+    assert_eq!(policy.format_value(&parse_input("1/7").unwrap().value), "1/7");
+    assert_eq!(policy.format_value(&parse_input("-22/7").unwrap().value), "-22/7");
+    assert_eq!(shown(&policy, "0.5"), "1/2");
+    assert_eq!(shown(&policy, "-1.25"), "-5/4");
+    assert_eq!(shown(&policy, "3.14159"), "314159/100000");
+    assert_eq!(shown(&policy, "7"), "7");
+    assert_eq!(shown(&policy, "0"), "0");
+}
+
+#[test]
+fn exact_fractions_follow_the_precision_too() {
+    let ten_thirds = RollValue::Rational(Rational::new(10, 3).unwrap());
+
+    assert_eq!(policy(RoundingMode::HalfUp, Precision::Places(2)).format_value(&ten_thirds), "3.33");
+    assert_eq!(policy(RoundingMode::HalfUp, Precision::Rational).format_value(&ten_thirds), "10/3");
+    assert_eq!(DisplayPolicy::new().format_value(&RollValue::Rational(Rational::from_integer(-2))), "-2");
+}
+
+#[test]
+fn modes_and_precisions_parse_from_their_names() {
+    assert_eq!("half-up".parse(), Ok(RoundingMode::HalfUp));
+    assert_eq!("Half-Even".parse(), Ok(RoundingMode::Bankers));
+    assert_eq!("floor".parse(), Ok(RoundingMode::Floor));
+    assert_eq!("TRUNCATE".parse(), Ok(RoundingMode::TowardZero));
+    assert!("nearest".parse::<RoundingMode>().is_err());
+
+    assert_eq!("0".parse(), Ok(Precision::Places(0)));
+    assert_eq!("28".parse(), Ok(Precision::Places(28)));
+    assert_eq!("Rational".parse(), Ok(Precision::Rational));
+    assert!("29".parse::<Precision>().is_err());
+    assert!("-1".parse::<Precision>().is_err());
+}
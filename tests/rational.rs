@@ -0,0 +1,51 @@
+use fluorite::parse::{parse_input, parse_input_with_context, ArithmeticMode, EvaluationContext, RollValue};
+use fluorite::rational::Rational;
+
+fn rational_context() -> EvaluationContext {
+    EvaluationContext {
+        arithmetic: ArithmeticMode::Rational,
+        ..EvaluationContext::new()
+    }
+}
+
+fn exact_value_of(input: &str) -> RollValue {
+    parse_input_with_context(input, &rational_context()).unwrap().value
+}
+
+#[test]
+fn thirds_multiply_back_to_one() {
+    assert_eq!(exact_value_of("(1/3)*3"), RollValue::Rational(Rational::from_integer(1)));
+    assert_ne!(parse_input("(1/3)*3").unwrap().value.number().unwrap().to_string(), "1");
+}
+
+#[test]
+fn fractions_stay_in_lowest_terms() {
+    assert_eq!(exact_value_of("10/4"), RollValue::Rational(Rational::new(5, 2).unwrap()));
+    assert_eq!(exact_value_of("0.25 + 1/4"), RollValue::Rational(Rational::new(1, 2).unwrap()));
+    assert_eq!(exact_value_of("-1/3 - 1/6"), RollValue::Rational(Rational::new(-1, 2).unwrap()));
+}
+
+#[test]
+fn remainder_matches_decimal_remainder() {
+    assert_eq!(exact_value_of("7 % 2.5"), RollValue::Rational(Rational::new(2, 1).unwrap()));
+    assert_eq!(exact_value_of("-7 % 2"), RollValue::Rational(Rational::new(-1, 1).unwrap()));
+}
+
+#[test]
+fn exact_values_work_as_dice_counts() {
+    let information = parse_input_with_context("(6/2)d6", &rational_context()).unwrap();
+    assert_eq!(information.pool.unwrap().len(), 3);
+}
+
+#[test]
+fn exact_division_by_zero_is_an_error() {
+    assert!(parse_input_with_context("1/(1/2 - 2/4)", &rational_context()).is_err());
+}
+
+#[test]
+fn rational_values_round_trip_through_json() {
+    let value = exact_value_of("10/3");
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(json, "\"10/3\"");
+    assert_eq!(serde_json::from_str::<RollValue>(&json).unwrap(), value);
+}
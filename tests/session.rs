@@ -0,0 +1,153 @@
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use fluorite::parse::{parse, EvaluationContext};
+use fluorite::session::{LogEntry, LogFilter, RngSource, SessionLog};
+use fluorite::storage::DataDir;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::env::temp_dir;
+use std::fs::{copy, create_dir_all, remove_dir_all, OpenOptions};
+use std::io::Write;
+
+fn scratch_dir(name: &str) -> DataDir {
+    let path = temp_dir().join(format!("fluorite-session-test-{}-{}", name, std::process::id()));
+    let _ = remove_dir_all(&path);
+    DataDir::new(path)
+}
+
+fn june(day: u32) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&NaiveDate::from_ymd_opt(2021, 6, day).unwrap().and_hms_opt(0, 0, 0).unwrap())
+}
+
+fn entry_at(day: u32, input: &str, profile: Option<&str>) -> LogEntry {
+    LogEntry {
+        time: june(day) + Duration::hours(12),
+        profile: profile.map(String::from),
+        ..LogEntry::new(input, Err(String::from("Not rolled.")), None)
+    }
+}
+
+fn inputs(entries: &[LogEntry]) -> Vec<&str> {
+    entries.iter().map(|entry| entry.input.as_str()).collect()
+}
+
+#[test]
+fn entries_are_appended_and_read_back_in_order() {
+    let data_dir = scratch_dir("append");
+    let log = data_dir.session_log();
+    assert!(log.entries().unwrap().is_empty());
+
+    let roll = parse("2d6 + 3").unwrap();
+    let (source, mut rng) = RngSource::from_entropy();
+    let entry = LogEntry {
+        shortcut: Some(String::from("Damage")),
+        ..LogEntry::new(roll.to_string(), roll.roll_with_rng(&EvaluationContext::new(), &mut rng).map_err(|e| e.to_string()), Some(source))
+    };
+    log.append(&entry).unwrap();
+    log.append(&entry_at(2, "1d4", None)).unwrap();
+
+    let entries = log.entries().unwrap();
+    assert_eq!(inputs(&entries), vec!["2d6 + 3", "1d4"]);
+    assert_eq!(entries[0].time, entry.time);
+    assert_eq!(entries[0].shortcut.as_deref(), Some("Damage"));
+    assert_eq!(entries[0].rng, Some(source));
+    assert_eq!(entries[0].result.as_ref().unwrap().processed_string, entry.result.as_ref().unwrap().processed_string);
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
+
+#[test]
+fn logged_seeds_reproduce_rolls() {
+    let roll = parse("10d20 + 4d6s").unwrap();
+    let context = EvaluationContext::new();
+    let (source, mut rng) = RngSource::from_entropy();
+    let original = roll.roll_with_rng(&context, &mut rng).unwrap();
+    let replayed = roll.roll_with_rng(&context, &mut StdRng::seed_from_u64(source.seed())).unwrap();
+
+    assert_eq!(replayed.processed_string, original.processed_string);
+}
+
+#[test]
+fn every_roll_in_a_seeded_sequence_logs_a_seed_which_reproduces_it() {
+    let roll = parse("10d20 + 4d6s").unwrap();
+    let context = EvaluationContext::new();
+    let mut sequence = StdRng::seed_from_u64(42);
+    let mut same_sequence = StdRng::seed_from_u64(42);
+    for _ in 0..3 {
+        let (source, mut rng) = RngSource::from_sequence(&mut sequence, 42);
+        let original = roll.roll_with_rng(&context, &mut rng).unwrap();
+        let replayed = roll.roll_with_rng(&context, &mut StdRng::seed_from_u64(source.seed())).unwrap();
+
+        assert_eq!(replayed.processed_string, original.processed_string);
+        assert_eq!(source.name(), "seeded");
+        assert_eq!(RngSource::from_sequence(&mut same_sequence, 42).0, source);
+    }
+}
+
+#[test]
+fn filters_pick_out_dates_profiles_and_the_last_few() {
+    let entries = vec![entry_at(1, "a", None), entry_at(2, "b", Some("Aria")), entry_at(3, "c", Some("Aria")), entry_at(4, "d", None)];
+
+    let between = LogFilter {
+        since: Some(june(2)),
+        until: Some(june(4)),
+        ..LogFilter::default()
+    };
+    assert_eq!(inputs(&between.apply(entries.clone())), vec!["b", "c"]);
+
+    let aria = LogFilter {
+        profile: Some(String::from("Aria")),
+        last: Some(1),
+        ..LogFilter::default()
+    };
+    assert_eq!(inputs(&aria.apply(entries.clone())), vec!["c"]);
+
+    let last = LogFilter {
+        last: Some(10),
+        until: Some(june(3) + Duration::hours(12)),
+        ..LogFilter::default()
+    };
+    assert_eq!(inputs(&last.apply(entries)), vec!["a", "b"]);
+}
+
+#[test]
+fn history_is_a_view_of_the_latest_entries() {
+    let data_dir = scratch_dir("history");
+    let log = data_dir.session_log();
+    for day in 1..=5 {
+        log.append(&entry_at(day, &format!("{}d6", day), None)).unwrap();
+    }
+
+    let history = log.history(2).unwrap();
+    assert_eq!(history.iter().map(|(input, _)| input.as_str()).collect::<Vec<&str>>(), vec!["4d6", "5d6"]);
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
+
+#[test]
+fn a_line_cut_short_is_skipped() {
+    let data_dir = scratch_dir("cut-short");
+    let log = data_dir.session_log();
+    log.append(&entry_at(1, "1d6", None)).unwrap();
+    OpenOptions::new().append(true).open(log.path()).unwrap().write_all(b"{\"time\":\"2021-06-02T12:00:00Z\",\"inp").unwrap();
+    let log = SessionLog::new(log.path());
+    log.append(&entry_at(3, "1d8", None)).unwrap();
+
+    assert_eq!(inputs(&log.entries().unwrap()), vec!["1d6", "1d8"]);
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
+
+#[test]
+fn older_history_starts_the_log() {
+    let data_dir = scratch_dir("import");
+    create_dir_all(data_dir.data_path()).unwrap();
+    copy("tests/fixtures/v0-original/history.json", data_dir.history().path()).unwrap();
+    let log = data_dir.session_log();
+
+    assert_eq!(log.import_history(&data_dir.history()).unwrap(), 3);
+    let entries = log.entries().unwrap();
+    assert_eq!(inputs(&entries), vec!["2d6+3", "1d20", "2d"]);
+    assert!(entries.iter().all(|entry| entry.rng.is_none()));
+
+    // Once there's a log, the old history is never imported again.
+    assert_eq!(log.import_history(&data_dir.history()).unwrap(), 0);
+    assert_eq!(log.entries().unwrap().len(), 3);
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
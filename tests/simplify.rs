@@ -0,0 +1,86 @@
+use fluorite::expr::{BinaryOperator, DiceExpr};
+use fluorite::parse::{parse, ArithmeticMode, CompiledRoll, EvaluationContext};
+use fluorite::simplify::simplify;
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+
+fn simplified(input: &str) -> String {
+    parse(input).unwrap().simplified(&EvaluationContext::new()).to_string()
+}
+
+#[test]
+fn constants_are_folded() {
+    assert_eq!(simplified("1d20 + 3 + 2 - 1"), "1d20 + 4");
+    assert_eq!(simplified("(1+1)d(3*2)"), "2d6");
+    assert_eq!(simplified("2 + 1d8 - 5"), "-3 + 1d8");
+    assert_eq!(simplified("1d8 - (2 - 5)"), "1d8 + 3");
+}
+
+#[test]
+fn like_dice_are_merged() {
+    assert_eq!(simplified("1d6+1d6"), "2d6");
+    assert_eq!(simplified("3d6 + 1d4 + 2d6 - 1d4 - 1d4"), "5d6 + 1d4 - 2d4");
+    assert_eq!(simplified("crit(3d6+2d6)"), "crit(5d6)");
+}
+
+#[test]
+fn unsafe_merges_are_left_alone() {
+    assert_eq!(simplified("1d20 + 1d20"), "1d20 + 1d20");
+    assert_eq!(simplified("1d6 - 1d6"), "1d6 - 1d6");
+    assert_eq!(simplified("(1d6+1d6)s"), "(1d6 + 1d6)s");
+    assert_eq!(simplified("1/0 + 2"), "1 / 0 + 2");
+
+    let context = EvaluationContext {
+        summarize_above: None,
+        ..EvaluationContext::new()
+    };
+    assert_eq!(parse("6000d6 + 6000d6").unwrap().simplified(&context).to_string(), "6000d6 + 6000d6");
+}
+
+#[test]
+fn sums_which_could_overflow_are_not_reordered() {
+    let max = Decimal::MAX.to_string();
+    let simplified_max = |input: &str| simplified(&input.replace("MAX", &max)).replace(&max, "MAX");
+
+    assert_eq!(simplified_max("MAX + 1d6 - MAX"), "MAX + 1d6 - MAX");
+    assert_eq!(simplified_max("1d6 + MAX - 3 + 2"), "1d6 + MAX - 3 + 2");
+    assert_eq!(simplified_max("1d6 - MAX - 3 - 2"), "1d6 - MAX - 5");
+    assert_eq!(simplified_max("MAX - 3 + 2 + 1d6 + 1d6 - 1d4 + 1d6"), "79228162514264337593543950334 + 2d6 - 1d4 + 1d6");
+    assert_eq!(simplified_max("1dMAX + 1dMAX"), "1dMAX + 1dMAX");
+}
+
+#[test]
+fn inexact_folds_follow_the_arithmetic_mode() {
+    let rational = EvaluationContext {
+        arithmetic: ArithmeticMode::Rational,
+        ..EvaluationContext::new()
+    };
+    assert_eq!(parse("1/4 + 1/4").unwrap().simplified(&rational).to_string(), "0.5");
+    assert_eq!(parse("1/3").unwrap().simplified(&rational).to_string(), "1 / 3");
+    assert_eq!(simplified("1/3"), "0.3333333333333333333333333333");
+}
+
+fn arithmetic() -> impl Strategy<Value = DiceExpr> {
+    let operator = prop_oneof![Just(BinaryOperator::Plus), Just(BinaryOperator::Minus), Just(BinaryOperator::Times), Just(BinaryOperator::Divide), Just(BinaryOperator::Mod)];
+    let number = (-1000i64..1000, 0u32..3).prop_map(|(mantissa, scale)| DiceExpr::Number(Decimal::new(mantissa, scale)));
+    number.prop_recursive(4, 24, 2, move |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|operand| DiceExpr::Negate(Box::new(operand))),
+            (operator.clone(), inner.clone(), inner).prop_map(|(operator, left, right)| DiceExpr::Binary(operator, Box::new(left), Box::new(right))),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn folding_keeps_the_value(expression in arithmetic(), exact in any::<bool>()) {
+        let context = EvaluationContext {
+            arithmetic: if exact { ArithmeticMode::Rational } else { ArithmeticMode::Decimal },
+            ..EvaluationContext::new()
+        };
+        if let Ok(original) = CompiledRoll::new(expression.clone()).roll(&context) {
+            let folded = CompiledRoll::new(simplify(&expression, &context)).roll(&context).unwrap();
+            prop_assert_eq!(folded.value, original.value);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use fluorite::error::StorageError;
+use fluorite::parse::{parse, EvaluationContext};
+use fluorite::storage::{Config, DataDir, RollShortcut, ShortcutsStore, BACKUP_COUNT};
+use std::env::temp_dir;
+use std::fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, write};
+
+/// A fresh data directory, named after the test using it so tests can run in parallel.
+fn scratch_dir(name: &str) -> DataDir {
+    let path = temp_dir().join(format!("fluorite-storage-test-{}-{}", name, std::process::id()));
+    let _ = remove_dir_all(&path);
+    DataDir::new(path)
+}
+
+#[test]
+fn missing_files_load_as_defaults() {
+    let data_dir = scratch_dir("missing");
+    assert_eq!(data_dir.config().load().unwrap(), Config::new());
+    assert!(data_dir.history().load().unwrap().is_empty());
+    assert!(data_dir.shortcuts().load().unwrap().is_empty());
+}
+
+#[test]
+fn stores_round_trip() {
+    let data_dir = scratch_dir("round-trip");
+    let config = Config {
+        max_history_entries: 5,
+        save_history: false,
+        ..Config::new()
+    };
+    let shortcuts = vec![RollShortcut {
+        name: String::from("Longsword attack"),
+        roll: String::from("1d20 + 5"),
+    }];
+    let history = vec![(String::from("2d6"), parse("2d6").unwrap().roll(&EvaluationContext::new()).map_err(|e| e.to_string())), (String::from("2d"), Err(String::from("Syntax error")))];
+
+    data_dir.config().save(&config).unwrap();
+    data_dir.shortcuts().save(&shortcuts).unwrap();
+    data_dir.history().save(&history).unwrap();
+
+    assert_eq!(data_dir.config().load().unwrap(), config);
+    assert_eq!(data_dir.shortcuts().load().unwrap(), shortcuts);
+    let loaded = data_dir.history().load().unwrap();
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded[0].1.as_ref().unwrap().processed_string, history[0].1.as_ref().unwrap().processed_string);
+    assert_eq!(loaded[1].1.as_ref().unwrap_err(), "Syntax error");
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
+
+#[test]
+fn older_configs_fill_in_new_fields() {
+    let data_dir = scratch_dir("older-config");
+    create_dir_all(data_dir.data_path()).unwrap();
+    write(data_dir.config().path(), r#"{"max_history_entries": 20, "save_history": true, "save_shortcuts": false}"#).unwrap();
+
+    let config = data_dir.config().load().unwrap();
+    assert_eq!(config.max_history_entries, 20);
+    assert!(!config.save_shortcuts);
+    assert_eq!(config.limits, Config::new().limits);
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
+
+#[test]
+fn corrupt_files_are_kept_and_reported() {
+    let data_dir = scratch_dir("corrupt");
+    create_dir_all(data_dir.data_path()).unwrap();
+    let store = data_dir.shortcuts();
+    write(store.path(), "{not json").unwrap();
+
+    let kept = match store.load() {
+        Err(StorageError::Corrupt { kept, .. }) => kept,
+        other => panic!("expected a corrupt file, got {:?}", other),
+    };
+    assert_eq!(read_to_string(&kept).unwrap(), "{not json");
+    // Loading it again doesn't pile up identical copies.
+    assert!(matches!(store.load(), Err(StorageError::Corrupt { kept: again, .. }) if again == kept));
+
+    write(store.path(), "[oops").unwrap();
+    assert!(matches!(store.load(), Err(StorageError::Corrupt { kept: other, .. }) if other != kept));
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
+
+#[test]
+fn saves_rotate_backups() {
+    let data_dir = scratch_dir("backups");
+    let store = data_dir.shortcuts();
+    let generations: Vec<Vec<RollShortcut>> = (0..5)
+        .map(|count| {
+            (0..count)
+                .map(|index| RollShortcut {
+                    name: format!("Shortcut {}", index),
+                    roll: String::from("1d6"),
+                })
+                .collect()
+        })
+        .collect();
+    for shortcuts in generations.iter() {
+        store.save(shortcuts).unwrap();
+    }
+
+    assert_eq!(store.load().unwrap(), generations[4]);
+    for generation in 1..=BACKUP_COUNT {
+        let backup = ShortcutsStore::new(store.backup_path(generation)).load().unwrap();
+        assert_eq!(backup, generations[4 - generation]);
+    }
+    assert!(!store.backup_path(BACKUP_COUNT + 1).exists());
+    assert_eq!(read_dir(data_dir.data_path()).unwrap().count(), 1 + BACKUP_COUNT, "only the file and its backups should be left");
+
+    write(store.path(), "").unwrap();
+    assert!(store.load().is_err());
+    assert_eq!(store.restore_backup().unwrap(), generations[3]);
+    remove_dir_all(data_dir.data_path()).unwrap();
+}
@@ -0,0 +1,138 @@
+use fluorite::error::RollError;
+use fluorite::parse::{parse, CritMode, EvaluationContext, RollInformation};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal::Decimal;
+
+fn context(summarize_above: Option<u64>) -> EvaluationContext {
+    EvaluationContext {
+        summarize_above,
+        ..EvaluationContext::new()
+    }
+}
+
+fn roll(input: &str, context: &EvaluationContext, seed: u64) -> Result<RollInformation, RollError> {
+    parse(input).unwrap().roll_with_rng(context, &mut StdRng::seed_from_u64(seed))
+}
+
+/// The dice count, sum, and face frequencies in a summary such as `[1200 dice: sum 4187, 1×197, 2×210, ...]`.
+fn summary(trace: &str) -> (u64, Decimal, Vec<(Decimal, u64)>) {
+    let (count, rest) = trace.strip_prefix('[').unwrap().split_once(" dice: sum ").unwrap();
+    let mut fields = rest.strip_suffix(']').unwrap().split(", ");
+    let sum = fields.next().unwrap().parse().unwrap();
+    let frequencies = fields
+        .map(|field| {
+            let (face, times) = field.split_once('×').unwrap();
+            (face.parse().unwrap(), times.parse().unwrap())
+        })
+        .collect();
+
+    (count.parse().unwrap(), sum, frequencies)
+}
+
+fn total(frequencies: &[(Decimal, u64)]) -> Decimal {
+    frequencies.iter().map(|(face, times)| face * Decimal::from(*times)).sum()
+}
+
+#[test]
+fn groups_over_the_threshold_are_summarized() {
+    let context = context(Some(10));
+
+    assert_eq!(roll("10d6", &context, 1).unwrap().pool.unwrap().len(), 10);
+    let information = roll("11d6", &context, 1).unwrap();
+    assert!(information.pool.is_none());
+    assert_eq!(summary(&information.processed_string).0, 11);
+}
+
+#[test]
+fn the_default_threshold_is_a_thousand_dice() {
+    let context = EvaluationContext::new();
+
+    assert_eq!(roll("1000d6", &context, 1).unwrap().pool.unwrap().len(), 1000);
+    assert!(roll("1001d6", &context, 1).unwrap().processed_string.starts_with("[1001 dice: sum "));
+}
+
+#[test]
+fn no_threshold_keeps_every_die() {
+    assert_eq!(roll("2000d6", &context(None), 1).unwrap().pool.unwrap().len(), 2000);
+}
+
+#[test]
+fn the_summary_counts_every_face_rolled_and_sums_them() {
+    let context = context(Some(10));
+    for seed in 0..20 {
+        let information = roll("500d8", &context, seed).unwrap();
+        let (count, sum, frequencies) = summary(&information.processed_string);
+
+        assert_eq!(count, 500);
+        assert_eq!(frequencies.iter().map(|(_, times)| times).sum::<u64>(), 500);
+        assert!(frequencies.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(frequencies.iter().all(|(face, times)| *face >= Decimal::from(1) && *face <= Decimal::from(8) && *times > 0));
+        assert_eq!(sum, total(&frequencies));
+        assert_eq!(information.value.number().unwrap(), sum);
+    }
+}
+
+#[test]
+fn summaries_take_part_in_arithmetic() {
+    let context = context(Some(10));
+    let (_, sum, _) = summary(&roll("20d6", &context, 4).unwrap().processed_string);
+
+    assert_eq!(roll("20d6 + 1", &context, 4).unwrap().value.number().unwrap(), sum + Decimal::from(1));
+}
+
+#[test]
+fn pool_modifiers_mean_the_same_on_summarized_groups() {
+    for input in ["30d6s", "30d6u", "30d6c6", "30d6m", "30d6us", "30d4mc4", "crit(30d6)m"].iter() {
+        for seed in 0..10 {
+            let listed = roll(input, &context(None), seed).unwrap();
+            let summarized = roll(input, &context(Some(10)), seed).unwrap();
+
+            assert_eq!(summarized.value.number().unwrap(), listed.value.number().unwrap(), "{} with seed {}", input, seed);
+        }
+    }
+}
+
+#[test]
+fn pool_modifiers_apply_above_the_default_threshold() {
+    let context = EvaluationContext::new();
+    let sorted = roll("2000d6s", &context, 1).unwrap();
+    let (before, after) = sorted.processed_string.split_once("s -> ").unwrap();
+
+    assert_eq!(before, after);
+    assert_eq!(summary(after).0, 2000);
+    let (_, _, frequencies) = summary(&roll("1001d6", &context, 1).unwrap().processed_string);
+    assert_eq!(roll("1001d6c6", &context, 1).unwrap().value.number().unwrap(), Decimal::from(frequencies[5].1));
+    assert_eq!(roll("1001d6u", &context, 1).unwrap().processed_string.split_once(" -> ").unwrap().1, "[6 dice: sum 21, 1×1, 2×1, 3×1, 4×1, 5×1, 6×1]");
+}
+
+#[test]
+fn doubled_crits_summarize_twice_as_many_dice() {
+    let context = EvaluationContext {
+        critical: true,
+        ..context(Some(10))
+    };
+    let information = roll("20d6", &context, 2).unwrap();
+    let (count, sum, _) = summary(&information.processed_string);
+
+    assert_eq!(count, 40);
+    assert_eq!(information.value.number().unwrap(), sum);
+}
+
+#[test]
+fn maximized_crits_add_a_summary_of_the_highest_faces() {
+    let context = EvaluationContext {
+        critical: true,
+        crit_mode: CritMode::Maximize,
+        ..context(Some(10))
+    };
+    for seed in 0..10 {
+        let information = roll("20d6", &context, seed).unwrap();
+        let (rolled, maximized) = information.processed_string.split_once(" + ").unwrap();
+        let (count, sum, _) = summary(rolled);
+
+        assert_eq!(count, 20);
+        assert_eq!(maximized, "[20 dice: sum 120, 6×20]");
+        assert_eq!(information.value.number().unwrap(), sum + Decimal::from(120));
+    }
+}
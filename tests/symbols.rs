@@ -0,0 +1,117 @@
+use fluorite::error::RollError;
+use fluorite::parse::{parse, parse_input, EvaluationContext, RollInformation, RollValue};
+use fluorite::registry::DiceRegistry;
+use fluorite::symbols::{Cancellation, SymbolPool};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+fn roll(input: &str, seed: u64) -> RollInformation {
+    parse(input).unwrap().roll_with_rng(&EvaluationContext::new(), &mut StdRng::seed_from_u64(seed)).unwrap()
+}
+
+fn symbols(information: &RollInformation) -> SymbolPool {
+    match &information.value {
+        RollValue::Symbols(symbols) => symbols.clone(),
+        other => panic!("Expected symbols, got {}", other),
+    }
+}
+
+/// The symbols on every face listed in a trace such as `[success, blank] + [failure+threat]`, before any cancel out.
+fn rolled_symbols(trace: &str) -> SymbolPool {
+    let mut pool = SymbolPool::new();
+    for face in trace.split(['[', ']', ',', '+']).map(str::trim) {
+        if !face.is_empty() && face != "blank" {
+            pool.add(face, 1);
+        }
+    }
+
+    pool
+}
+
+fn pool_of(symbols: &[(&str, i64)]) -> SymbolPool {
+    let mut pool = SymbolPool::new();
+    for (symbol, count) in symbols {
+        pool.add(symbol, *count);
+    }
+
+    pool
+}
+
+#[test]
+fn opposing_symbols_cancel_one_for_one() {
+    let cancellations = DiceRegistry::new().cancellations;
+    let pool = pool_of(&[("success", 3), ("failure", 1), ("threat", 2), ("triumph", 1)]);
+    let net = pool.cancelled(&cancellations);
+
+    assert_eq!(net, pool_of(&[("success", 2), ("threat", 2), ("triumph", 1)]));
+    assert_eq!(net.to_string(), "2 success, 2 threat, 1 triumph");
+}
+
+#[test]
+fn evenly_matched_symbols_leave_nothing() {
+    let net = pool_of(&[("success", 2), ("failure", 2), ("advantage", 1), ("threat", 1)]).cancelled(&DiceRegistry::new().cancellations);
+
+    assert_eq!(net, SymbolPool::new());
+    assert_eq!(net.to_string(), "no symbols");
+}
+
+#[test]
+fn rolls_show_the_net_symbols_of_every_face_rolled() {
+    for seed in 0..200 {
+        let information = roll("3dability + 2dproficiency + 2ddifficulty + 1dchallenge + 1dboost + 1dsetback", seed);
+        let net = symbols(&information);
+
+        assert_eq!(net, rolled_symbols(&information.processed_string).cancelled(&DiceRegistry::new().cancellations), "{}", information.processed_string);
+        assert!(net.count("success") == 0 || net.count("failure") == 0);
+        assert!(net.count("advantage") == 0 || net.count("threat") == 0);
+    }
+}
+
+#[test]
+fn triumph_and_despair_come_with_a_success_and_a_failure() {
+    let (mut triumphs, mut despairs) = (0, 0);
+    for seed in 0..200 {
+        let proficiency = roll("1dproficiency", seed);
+        if symbols(&proficiency).count("triumph") > 0 {
+            assert_eq!(proficiency.processed_string, "[triumph+success]");
+            triumphs += 1;
+        }
+        let challenge = roll("1dchallenge", seed);
+        if symbols(&challenge).count("despair") > 0 {
+            assert_eq!(challenge.processed_string, "[despair+failure]");
+            despairs += 1;
+        }
+    }
+
+    assert!(triumphs > 0 && despairs > 0);
+}
+
+#[test]
+fn the_built_in_dice_have_genesys_faces() {
+    let registry = DiceRegistry::new();
+    for (name, faces) in [("boost", 6), ("setback", 6), ("ability", 8), ("difficulty", 8), ("proficiency", 12), ("challenge", 12)].iter() {
+        let die = registry.get(name).unwrap();
+        assert_eq!(die.faces.len(), *faces, "{}", name);
+        assert!(die.is_symbolic());
+    }
+    assert!(registry.get("Ability").is_some());
+}
+
+#[test]
+fn custom_cancellations_apply_alongside_the_built_in_ones() {
+    let pool = pool_of(&[("light", 3), ("dark", 1), ("success", 1), ("failure", 1)]);
+    let mut cancellations = DiceRegistry::new().cancellations;
+    cancellations.push(Cancellation::new("light", "dark"));
+
+    assert_eq!(pool.cancelled(&cancellations), pool_of(&[("light", 2)]));
+}
+
+#[test]
+fn symbols_can_only_be_added_to_symbols() {
+    for input in ["1dboost + 1", "2 * 1dability", "1dability - 1ddifficulty", "(1dboost)d6"].iter() {
+        match parse_input(input) {
+            Err(RollError::Evaluation(_)) => (),
+            other => panic!("Expected an evaluation error for {}, got {:?}", input, other),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use fluorite::error::RollError;
+use fluorite::limits::ResourceLimits;
+use fluorite::parse::RollInformation;
+use fluorite::systems::GameSystem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal::Decimal;
+use std::collections::BTreeSet;
+
+const SEEDS: u64 = 500;
+
+fn roll(system: GameSystem, parameter: i64, seed: u64) -> RollInformation {
+    system.roll_with_rng(parameter, &ResourceLimits::new(), &mut StdRng::seed_from_u64(seed)).unwrap()
+}
+
+/// The faces in each bracketed list in a trace, such as `[3, 5] + 1 vs [2, 9]`.
+fn dice_lists(trace: &str) -> Vec<Vec<i64>> {
+    trace.split('[').skip(1).map(|list| list.split(']').next().unwrap().split(", ").map(|face| face.parse().unwrap()).collect()).collect()
+}
+
+/// Rolls `system` with every seed, checking each result against `expected`, and returns the set of outcomes seen.
+fn outcomes(system: GameSystem, parameter: i64, expected: impl Fn(&[Vec<i64>]) -> String) -> BTreeSet<String> {
+    let mut seen = BTreeSet::new();
+    for seed in 0..SEEDS {
+        let information = roll(system, parameter, seed);
+        let outcome = information.outcome.clone().unwrap();
+        assert_eq!(outcome, expected(&dice_lists(&information.processed_string)), "{} rolled {}", system.name(), information.processed_string);
+        seen.insert(outcome);
+    }
+
+    seen
+}
+
+fn count(dice: &[i64], face: impl Fn(i64) -> bool) -> usize {
+    dice.iter().filter(|die| face(**die)).count()
+}
+
+fn assert_seen(seen: &BTreeSet<String>, expected: &[&str]) {
+    for outcome in expected {
+        assert!(seen.contains(*outcome), "never saw {}; saw {:?}", outcome, seen);
+    }
+}
+
+#[test]
+fn shadowrun_glitches_when_more_than_half_the_dice_are_ones() {
+    for pool in 1..=4 {
+        let seen = outcomes(GameSystem::Shadowrun, pool, |lists| {
+            let dice = &lists[0];
+            let (hits, ones) = (count(dice, |die| die >= 5), count(dice, |die| die == 1));
+            match (ones * 2 > dice.len(), hits) {
+                (true, 0) => String::from("Critical glitch"),
+                (true, 1) => String::from("Glitch (1 hit)"),
+                (true, _) => format!("Glitch ({} hits)", hits),
+                (false, 0) => String::from("No hits"),
+                (false, 1) => String::from("1 hit"),
+                (false, _) => format!("{} hits", hits),
+            }
+        });
+        assert!(seen.contains("Critical glitch"), "pool of {} never critically glitched", pool);
+        if pool == 3 {
+            assert_seen(&seen, &["Glitch (1 hit)", "No hits", "1 hit", "2 hits"]);
+        }
+    }
+}
+
+#[test]
+fn shadowrun_hits_are_the_value() {
+    for seed in 0..50 {
+        let information = roll(GameSystem::Shadowrun, 6, seed);
+        let hits = count(&dice_lists(&information.processed_string)[0], |die| die >= 5);
+        assert_eq!(information.value.number().unwrap(), Decimal::from(hits));
+    }
+}
+
+#[test]
+fn blades_reads_the_highest_die_with_two_sixes_a_critical() {
+    let seen = outcomes(GameSystem::Blades, 3, |lists| {
+        let dice = &lists[0];
+        let highest = *dice.iter().max().unwrap();
+        match (count(dice, |die| die == 6), highest) {
+            (sixes, _) if sixes >= 2 => "Critical success",
+            (_, 6) => "Full success",
+            (_, 4..=5) => "Partial success",
+            _ => "Bad outcome",
+        }
+        .to_string()
+    });
+
+    assert_seen(&seen, &["Critical success", "Full success", "Partial success", "Bad outcome"]);
+}
+
+#[test]
+fn blades_with_no_dice_takes_the_lower_of_two_and_never_crits() {
+    let seen = outcomes(GameSystem::Blades, 0, |lists| {
+        match *lists[0].iter().min().unwrap() {
+            6 => "Full success",
+            4..=5 => "Partial success",
+            _ => "Bad outcome",
+        }
+        .to_string()
+    });
+
+    assert!(!seen.contains("Critical success"));
+    assert_seen(&seen, &["Full success", "Partial success", "Bad outcome"]);
+    assert!(roll(GameSystem::Blades, 0, 1).processed_string.starts_with("lowest of ["));
+}
+
+#[test]
+fn chronicles_counts_eights_and_up_with_ten_again() {
+    let seen = outcomes(GameSystem::Chronicles, 8, |lists| {
+        let successes = lists.iter().map(|dice| count(dice, |die| die >= 8)).sum::<usize>();
+        // Every 10 is rolled again, so there's one more list of dice for each list containing a 10, until one doesn't.
+        for pair in lists.windows(2) {
+            assert_eq!(count(&pair[0], |die| die == 10), pair[1].len());
+        }
+        assert_eq!(count(lists.last().unwrap(), |die| die == 10), 0);
+        match successes {
+            0 => "Failure",
+            1..=4 => "Success",
+            _ => "Exceptional success",
+        }
+        .to_string()
+    });
+
+    assert_seen(&seen, &["Failure", "Success", "Exceptional success"]);
+}
+
+#[test]
+fn a_chronicles_chance_die_succeeds_only_on_a_ten_and_fails_dramatically_on_a_one() {
+    let seen = outcomes(GameSystem::Chronicles, 0, |lists| {
+        match lists[0][0] {
+            10 => "Success",
+            1 => "Dramatic failure",
+            _ => "Failure",
+        }
+        .to_string()
+    });
+
+    assert_seen(&seen, &["Success", "Dramatic failure", "Failure"]);
+}
+
+#[test]
+fn pbta_grades_the_total_with_the_modifier() {
+    for modifier in -2..=3 {
+        let seen = outcomes(GameSystem::Pbta, modifier, |lists| {
+            let total = lists[0].iter().sum::<i64>() + modifier;
+            match total {
+                10..=i64::MAX => "Strong hit",
+                7..=9 => "Weak hit",
+                _ => "Miss",
+            }
+            .to_string()
+        });
+        assert_seen(&seen, &["Strong hit", "Weak hit", "Miss"]);
+    }
+}
+
+#[test]
+fn ironsworn_beats_challenge_dice_and_notes_matches() {
+    let seen = outcomes(GameSystem::Ironsworn, -1, |lists| {
+        let (action, challenge) = (lists[0][0], &lists[1]);
+        let score = (action - 1).min(10);
+        let outcome = match count(challenge, |die| score > die) {
+            2 => "Strong hit",
+            1 => "Weak hit",
+            _ => "Miss",
+        };
+        match challenge[0] == challenge[1] {
+            true => format!("{} with a match", outcome),
+            false => String::from(outcome),
+        }
+    });
+
+    assert_seen(&seen, &["Strong hit", "Weak hit", "Miss", "Miss with a match"]);
+}
+
+#[test]
+fn negative_pools_are_errors() {
+    for system in [GameSystem::Chronicles, GameSystem::Shadowrun, GameSystem::Blades].iter() {
+        match system.roll_with_rng(-1, &ResourceLimits::new(), &mut StdRng::seed_from_u64(1)) {
+            Err(RollError::Evaluation(_)) => (),
+            other => panic!("Expected an evaluation error for {}, got {:?}", system.name(), other),
+        }
+    }
+}